@@ -3,22 +3,34 @@
 
 #[ink::contract]
 mod token_swap {
-    use ink::storage::Mapping;
+    use ink::prelude::vec::Vec;
+    use ink::storage::{Lazy, Mapping};
+    #[cfg(not(test))]
     use ink::LangError;
+    #[cfg(not(test))]
     use ink_env::call::{build_call, ExecutionInput, Selector};
     use ink_env::DefaultEnvironment;
 
-    pub type Swap = (
-        AccountId,         // creator
-        AccountId,         // token_a
-        AccountId,         // token_b
-        Balance,           // amount_a
-        Balance,           // amount_b
-        BlockNumber,       // expiration
-        Balance,           // Amount of Token A already accepted
-        Balance,           // Amount of Token B already accepted
-        Option<AccountId>, // Allowed acceptor
-    );
+    // Field order matches the former 9-tuple, so the SCALE encoding of stored swaps
+    // is unchanged and no storage migration is needed.
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(
+        feature = "std",
+        derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+    )]
+    pub struct Swap {
+        pub creator: AccountId,
+        pub token_a: AccountId,
+        pub token_b: AccountId,
+        pub required_a: Balance,
+        pub required_b: Balance,
+        pub expiration: BlockNumber,
+        // Amount of Token A already accepted
+        pub accepted_a: Balance,
+        // Amount of Token B already accepted
+        pub accepted_b: Balance,
+        pub allowed_acceptor: Option<AccountId>,
+    }
 
     #[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
     #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
@@ -31,16 +43,229 @@ mod token_swap {
         CallFailed,
         DelegateFailed,
         DelegateFunctionFailed,
+        ZeroAddress,
+        SelfAccept,
+        AmountExceedsSupply,
+        DelegationActive,
+        NoteTooLong,
+        InvalidAmount,
+        LocalSwapsExist,
+        PriceOutOfBand,
+        NoNativeWrapper,
+        SwapCompleted,
+        TokenSwapLimitReached,
+        CallbackFailed,
+        Reentrancy,
+        IdenticalTokens,
+        ZeroAmount,
+        SwapNotExpired,
+        Overflow,
+        NoOracle,
+        Paused,
+        Frozen,
+        MustFillEntirely,
+        DurationTooLong,
+        BatchTooLarge,
+        FillTooSmall,
+        UnderPriced,
+        OracleSettlementDisabled,
+        SwapAlreadyFilled,
     }
 
     pub type Result<T> = core::result::Result<T, Error>;
 
+    // (acceptor, swap id) -> (amount_a, amount_b) filled by that acceptor
+    type FillMap = Mapping<(AccountId, u64), (Balance, Balance)>;
+
+    #[derive(Debug, Clone, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct Config {
+        pub owner: AccountId,
+        pub delegated_contract: Option<AccountId>,
+        pub allow_self_accept: bool,
+        pub check_total_supply: bool,
+        pub oracle: Option<AccountId>,
+        pub max_deviation_bps: u32,
+        pub native_wrapper: Option<AccountId>,
+        pub max_open_per_token: u32,
+        pub creation_fee: Balance,
+        pub call_gas_limit: u64,
+        pub max_call_gas_limit: u64,
+        pub complete_if_below: Balance,
+        pub max_duration: BlockNumber,
+        pub paused: bool,
+        pub frozen: bool,
+    }
+
+    // Fields left as None are not changed by set_config; Some(None) clears an optional account.
+    #[derive(Debug, Clone, Default, PartialEq, Eq, scale::Encode, scale::Decode)]
+    #[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+    pub struct ConfigUpdate {
+        pub delegated_contract: Option<Option<AccountId>>,
+        pub allow_self_accept: Option<bool>,
+        pub check_total_supply: Option<bool>,
+        pub oracle: Option<Option<AccountId>>,
+        pub max_deviation_bps: Option<u32>,
+        pub native_wrapper: Option<Option<AccountId>>,
+        pub max_open_per_token: Option<u32>,
+        pub creation_fee: Option<Balance>,
+        pub call_gas_limit: Option<u64>,
+        pub max_call_gas_limit: Option<u64>,
+        pub complete_if_below: Option<Balance>,
+        pub max_duration: Option<BlockNumber>,
+    }
+
+    // Full contract state, for off-chain invariant checks only.
+    #[cfg(feature = "std")]
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub struct StateSnapshot {
+        pub swaps: Vec<(u64, Swap)>,
+        pub swap_count: u64,
+        pub active_swaps: u64,
+        pub config: Config,
+    }
+
+    // Upper bound on the number of ids walked by the read-only scans below.
+    const MAX_SCAN: u64 = 1_000;
+    // Upper bound on the number of ids accepted by get_swaps.
+    const MAX_SWAPS_PER_QUERY: usize = 50;
+    // Upper bound on the number of accounts accepted by open_counts.
+    const MAX_ACCOUNTS_PER_QUERY: usize = 50;
+    // Upper bound on the number of ids walked by one list_swaps page.
+    const MAX_LIST_LIMIT: u64 = 100;
+    // Upper bound on the size of a swap note, in bytes.
+    const MAX_NOTE_LEN: usize = 256;
+    // Upper bound on the number of buckets returned by expiry_histogram.
+    const MAX_HISTOGRAM_BUCKETS: u32 = 100;
+    // Upper bound on the number of fills accepted by accept_swaps.
+    const MAX_BATCH: usize = 20;
+
+    // Oracle prices are token B per unit of token A, scaled by PRICE_SCALE.
+    const PRICE_SELECTOR: [u8; 4] = ink::selector_bytes!("price");
+    const PRICE_SCALE: Balance = 1_000_000_000_000;
+    const DEFAULT_MAX_DEVIATION_BPS: u32 = 500;
+
+    // Wraps the transferred native value into the wrapper token, credited to the caller.
+    const DEPOSIT_SELECTOR: [u8; 4] = ink::selector_bytes!("deposit");
+
+    // Called on contract acceptors of swaps with the callback enabled.
+    const ON_SWAP_ACCEPTED_SELECTOR: [u8; 4] = ink::selector_bytes!("on_swap_accepted");
+
+    // Default gas (ref_time) forwarded to each cross-contract call.
+    const DEFAULT_CALL_GAS_LIMIT: u64 = 10_000_000_000;
+
+    // Default upper bound on a swap's duration: about 30 days of 6 second blocks.
+    const DEFAULT_MAX_DURATION: BlockNumber = 432_000;
+
+    // Token selectors used for cross-contract calls.
+    const BALANCE_OF_SELECTOR: [u8; 4] = ink::selector_bytes!("balance_of");
+    const TRANSFER_SELECTOR: [u8; 4] = ink::selector_bytes!("transfer");
+    const TOTAL_SUPPLY_SELECTOR: [u8; 4] = ink::selector_bytes!("total_supply");
+    const TOKEN_SYMBOL_SELECTOR: [u8; 4] = ink::selector_bytes!("token_symbol");
+
+    // Full 256-bit product of two balances as (high, low) halves, which compare in order.
+    // Two 18-decimal amounts already overflow a u128 product.
+    fn widening_mul(a: Balance, b: Balance) -> (Balance, Balance) {
+        const LOW: Balance = u64::MAX as Balance;
+        let (a_high, a_low) = (a >> 64, a & LOW);
+        let (b_high, b_low) = (b >> 64, b & LOW);
+        let low_low = a_low * b_low;
+        let high_low = a_high * b_low;
+        let low_high = a_low * b_high;
+        let middle = (low_low >> 64) + (high_low & LOW) + (low_high & LOW);
+        (
+            a_high * b_high + (high_low >> 64) + (low_high >> 64) + (middle >> 64),
+            (low_low & LOW) | (middle << 64),
+        )
+    }
+
+    // (a * b / c, a * b % c) through a 256-bit product, or None when c is 0 or the quotient
+    // does not fit in a Balance.
+    fn mul_div(a: Balance, b: Balance, c: Balance) -> Option<(Balance, Balance)> {
+        let (mut remainder, low) = widening_mul(a, b);
+        if c == 0 || remainder >= c {
+            return None;
+        }
+        let mut quotient: Balance = 0;
+        for bit in (0..128).rev() {
+            let carry = remainder >> 127;
+            remainder = (remainder << 1) | ((low >> bit) & 1);
+            quotient <<= 1;
+            if carry == 1 || remainder >= c {
+                remainder = remainder.wrapping_sub(c);
+                quotient |= 1;
+            }
+        }
+        Some((quotient, remainder))
+    }
+
     #[ink(storage)]
     pub struct TokenSwap {
         pub swaps: Mapping<u64, Swap>,
         pub swap_count: u64,
+        // swaps created and neither removed nor fully filled; swap_count is only the id source
+        active_swaps: u64,
+        // no swap below this id is stored; advanced by remove_swap
+        oldest_id: u64,
+        // successful accept_swap calls across all swaps
+        total_fills: u64,
+        fills: FillMap,
+        // swap id -> (amount_a, amount_b) as listed at creation, with amount_a as actually
+        // escrowed for safe_create_swap; never rewritten, and kept once the swap completes
+        target_ratios: Mapping<u64, (Balance, Balance)>,
+        // swap id -> final (accepted_a, accepted_b) of a fully filled swap. Completed swaps are
+        // removed from `swaps`, so proceeds and average_price read them here.
+        filled_totals: Mapping<u64, (Balance, Balance)>,
+        // swap id -> token A that actually arrived in escrow at creation, which a
+        // fee-on-transfer token leaves below required_a
+        escrowed_a: Mapping<u64, Balance>,
+        // swap id -> widest gap, in bps, between the oracle rate and the listed price at which
+        // the creator accepts accept_swap_at_oracle_rate; absent disables it for the swap
+        oracle_bands: Mapping<u64, u32>,
+        // swap id -> opaque bytes, typically encrypted for the counterparty
+        notes: Mapping<u64, Vec<u8>>,
+        // swap ids whose contract acceptor is notified through on_swap_accepted
+        acceptor_callbacks: Mapping<u64, bool>,
+        // swap id -> (min_a, min_b) per fill, waived for the fill that completes the swap
+        min_fills: Mapping<u64, (Balance, Balance)>,
+        // token -> symbol, cached by pair_symbols
+        symbols: Mapping<AccountId, Vec<u8>>,
+        completed_by_creator: Mapping<AccountId, u64>,
+        // creator -> number of swaps still in storage
+        open_by_creator: Mapping<AccountId, u64>,
+        // token_a -> number of swaps still in storage offering it
+        open_by_token: Mapping<AccountId, u32>,
+        // token_b -> number of swaps still in storage asking for it
+        open_by_token_b: Mapping<AccountId, u32>,
         delegated_contract: Option<AccountId>,
         owner: AccountId,
+        // set by transfer_ownership until the new owner accepts
+        pending_owner: Option<AccountId>,
+        allow_self_accept: bool,
+        check_total_supply: bool,
+        oracle: Option<AccountId>,
+        max_deviation_bps: u32,
+        native_wrapper: Option<AccountId>,
+        // 0 means no cap
+        max_open_per_token: u32,
+        // set while an accept is in progress; a Lazy is written to its own cell at once, so a
+        // re-entrant call sees it even though the root struct is only saved after the message
+        locked: Lazy<bool>,
+        // native value required with each create_swap
+        creation_fee: Balance,
+        collected_fees: Balance,
+        // gas forwarded to each cross-contract call; 0 forwards all remaining gas
+        call_gas_limit: u64,
+        // owner-set cap on the gas actually forwarded, 0 for none
+        max_call_gas_limit: u64,
+        // blocks create_swap and accepts; cancels and refunds stay available
+        paused: bool,
+        // blocks every swap mutation, cancels and refunds included
+        frozen: bool,
+        // fills leaving a nonzero remainder below this on either side are rejected; 0 disables
+        complete_if_below: Balance,
+        // longest duration accepted by create_swap, in blocks
+        max_duration: BlockNumber,
     }
 
     #[ink(event)]
@@ -57,6 +282,64 @@ mod token_swap {
         id: u64,
         #[ink(topic)]
         acceptor: AccountId,
+        // filled by this call
+        amount_a: Balance,
+        amount_b: Balance,
+        // cumulative after this call
+        accepted_a: Balance,
+        accepted_b: Balance,
+        completed: bool,
+        remaining_a: Balance,
+        remaining_b: Balance,
+    }
+
+    #[ink(event)]
+    pub struct SwapFullyFilled {
+        #[ink(topic)]
+        id: u64,
+        last_acceptor: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct AcceptorUpdated {
+        #[ink(topic)]
+        id: u64,
+    }
+
+    #[ink(event)]
+    pub struct SwapExpiredReaped {
+        #[ink(topic)]
+        id: u64,
+        #[ink(topic)]
+        reaper: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct Paused {
+        account: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct Unpaused {
+        account: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct Frozen {
+        account: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct Unfrozen {
+        account: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct OwnershipTransferred {
+        #[ink(topic)]
+        previous_owner: AccountId,
+        #[ink(topic)]
+        new_owner: AccountId,
     }
 
     #[ink(event)]
@@ -71,237 +354,3620 @@ mod token_swap {
             Self {
                 swaps: Default::default(),
                 swap_count: 0,
+                oldest_id: 0,
+                active_swaps: 0,
+                total_fills: 0,
+                fills: Default::default(),
+                target_ratios: Default::default(),
+                filled_totals: Default::default(),
+                oracle_bands: Default::default(),
+                escrowed_a: Default::default(),
+                notes: Default::default(),
+                acceptor_callbacks: Default::default(),
+                min_fills: Default::default(),
+                symbols: Default::default(),
+                completed_by_creator: Default::default(),
+                open_by_creator: Default::default(),
+                open_by_token: Default::default(),
+                open_by_token_b: Default::default(),
                 delegated_contract: None,
                 owner: Self::env().caller(),
+                pending_owner: None,
+                allow_self_accept: false,
+                check_total_supply: false,
+                oracle: None,
+                max_deviation_bps: DEFAULT_MAX_DEVIATION_BPS,
+                native_wrapper: None,
+                max_open_per_token: 0,
+                locked: Default::default(),
+                creation_fee: 0,
+                collected_fees: 0,
+                call_gas_limit: DEFAULT_CALL_GAS_LIMIT,
+                max_call_gas_limit: 0,
+                paused: false,
+                frozen: false,
+                complete_if_below: 0,
+                max_duration: DEFAULT_MAX_DURATION,
             }
         }
 
+        // Delegation can only be enabled before any swap is held locally, since
+        // accept_swap and delete_swap only ever operate on local swaps.
         #[ink(message)]
-        pub fn set_delegated_contract(&mut self, contract: AccountId) {
-            if self.env().caller() != self.owner {
-                ink_env::debug_println!("Unauthorized");
-                return;
+        pub fn set_delegated_contract(&mut self, contract: AccountId) -> Result<()> {
+            self.ensure_owner()?;
+            if self.has_local_swaps() {
+                return Err(Error::LocalSwapsExist);
             }
             self.delegated_contract = Some(contract);
+            Ok(())
         }
 
-        fn get_balance(&self, token_contract: AccountId, account: AccountId) -> Result<Balance> {
-            let result: core::result::Result<
-                core::result::Result<Balance, LangError>,
-                ink_env::Error,
-            > = build_call::<DefaultEnvironment>()
-                .call(token_contract)
-                .gas_limit(5000)
-                .transferred_value(0)
-                .exec_input(
-                    ExecutionInput::new(Selector::new(ink::selector_bytes!("balance_of")))
-                        .push_arg(account),
-                )
-                .returns::<Balance>()
-                .try_invoke();
+        #[ink(message)]
+        pub fn owner(&self) -> AccountId {
+            self.owner
+        }
 
-            match result {
-                Ok(Ok(balance)) => Ok(balance),
-                Ok(Err(_)) => Err(Error::InsufficientBalance),
-                Err(_) => Err(Error::CallFailed),
+        // Ownership only moves once new_owner calls accept_ownership.
+        #[ink(message)]
+        pub fn transfer_ownership(&mut self, new_owner: AccountId) -> Result<()> {
+            self.ensure_owner()?;
+            self.pending_owner = Some(new_owner);
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn accept_ownership(&mut self) -> Result<()> {
+            let caller = self.env().caller();
+            if self.pending_owner != Some(caller) {
+                return Err(Error::Unauthorized);
             }
+
+            let previous_owner = self.owner;
+            self.owner = caller;
+            self.pending_owner = None;
+
+            self.env().emit_event(OwnershipTransferred {
+                previous_owner,
+                new_owner: caller,
+            });
+
+            Ok(())
         }
 
-        pub fn create_swap(
-            &mut self,
-            token_a: AccountId,
-            token_b: AccountId,
-            amount_a: Balance,
-            amount_b: Balance,
-            duration: BlockNumber,
-            allowed_acceptor: Option<AccountId>, // Nouvel argument
-        ) -> Result<u64> {
-            if let Some(delegate) = self.delegated_contract {
-                let selector = ink::selector_bytes!("create_swap_delegate");
-                let nested_result: core::result::Result<
-                    core::result::Result<(), LangError>,
-                    ink_env::Error,
-                > = build_call::<DefaultEnvironment>()
-                    .call(delegate)
-                    .gas_limit(5000)
-                    .transferred_value(0)
-                    .exec_input(
-                        ExecutionInput::new(Selector::new(selector))
-                            .push_arg(token_a)
-                            .push_arg(token_b)
-                            .push_arg(amount_a)
-                            .push_arg(amount_b)
-                            .push_arg(duration),
-                    )
-                    .returns::<()>()
-                    .try_invoke();
+        #[ink(message)]
+        pub fn pause(&mut self) -> Result<()> {
+            self.ensure_owner()?;
+            self.paused = true;
+            self.env().emit_event(Paused {
+                account: self.env().caller(),
+            });
+            Ok(())
+        }
 
-                let result = match nested_result {
-                    Ok(inner_result) => inner_result.map_err(|_| Error::DelegateFunctionFailed),
-                    Err(_) => Err(Error::DelegateFailed),
-                };
+        #[ink(message)]
+        pub fn unpause(&mut self) -> Result<()> {
+            self.ensure_owner()?;
+            self.paused = false;
+            self.env().emit_event(Unpaused {
+                account: self.env().caller(),
+            });
+            Ok(())
+        }
 
-                match result {
-                    Ok(()) => Ok(self.swap_count),
-                    Err(e) => Err(e),
-                }
-            } else {
-                let caller = self.env().caller();
-                let balance_a: Balance = self.get_balance(token_a, caller)?;
+        // Emergency stop for exploit investigations: unlike pause, it also blocks
+        // cancels, refunds and reaping, leaving swaps untouched until unfreeze.
+        #[ink(message)]
+        pub fn freeze_all(&mut self) -> Result<()> {
+            self.ensure_owner()?;
+            self.frozen = true;
+            self.env().emit_event(Frozen {
+                account: self.env().caller(),
+            });
+            Ok(())
+        }
 
-                if balance_a < amount_a {
-                    return Err(Error::InsufficientBalance);
-                }
+        #[ink(message)]
+        pub fn unfreeze(&mut self) -> Result<()> {
+            self.ensure_owner()?;
+            self.frozen = false;
+            self.env().emit_event(Unfrozen {
+                account: self.env().caller(),
+            });
+            Ok(())
+        }
 
-                let balance_b: Balance = self.get_balance(token_b, caller)?;
-                if balance_b < amount_b {
-                    return Err(Error::InsufficientBalance);
+        #[ink(message)]
+        pub fn config(&self) -> Config {
+            Config {
+                owner: self.owner,
+                delegated_contract: self.delegated_contract,
+                allow_self_accept: self.allow_self_accept,
+                check_total_supply: self.check_total_supply,
+                oracle: self.oracle,
+                max_deviation_bps: self.max_deviation_bps,
+                native_wrapper: self.native_wrapper,
+                max_open_per_token: self.max_open_per_token,
+                creation_fee: self.creation_fee,
+                call_gas_limit: self.call_gas_limit,
+                max_call_gas_limit: self.max_call_gas_limit,
+                complete_if_below: self.complete_if_below,
+                max_duration: self.max_duration,
+                paused: self.paused,
+                frozen: self.frozen,
+            }
+        }
+
+        #[ink(message)]
+        pub fn set_config(&mut self, update: ConfigUpdate) -> Result<()> {
+            self.ensure_owner()?;
+            if let Some(contract) = update.delegated_contract {
+                if self.has_local_swaps() {
+                    return Err(Error::LocalSwapsExist);
                 }
+                self.delegated_contract = contract;
+            }
+            if let Some(allowed) = update.allow_self_accept {
+                self.allow_self_accept = allowed;
+            }
+            if let Some(enabled) = update.check_total_supply {
+                self.check_total_supply = enabled;
+            }
+            if let Some(oracle) = update.oracle {
+                self.oracle = oracle;
+            }
+            if let Some(bps) = update.max_deviation_bps {
+                self.max_deviation_bps = bps;
+            }
+            if let Some(wrapper) = update.native_wrapper {
+                self.native_wrapper = wrapper;
+            }
+            if let Some(max) = update.max_open_per_token {
+                self.max_open_per_token = max;
+            }
+            if let Some(fee) = update.creation_fee {
+                self.creation_fee = fee;
+            }
+            if let Some(gas_limit) = update.call_gas_limit {
+                self.call_gas_limit = gas_limit;
+            }
+            if let Some(cap) = update.max_call_gas_limit {
+                self.max_call_gas_limit = cap;
+            }
+            if let Some(threshold) = update.complete_if_below {
+                self.complete_if_below = threshold;
+            }
+            if let Some(duration) = update.max_duration {
+                self.max_duration = duration;
+            }
+            Ok(())
+        }
 
-                self.transfer_token(token_a, caller, self.env().account_id(), amount_a)?;
+        #[ink(message)]
+        pub fn set_allow_self_accept(&mut self, allowed: bool) -> Result<()> {
+            self.ensure_owner()?;
+            self.allow_self_accept = allowed;
+            Ok(())
+        }
 
-                let expiration = self
-                    .env()
-                    .block_number()
-                    .checked_add(duration)
-                    .ok_or(Error::CallFailed)?;
+        #[ink(message)]
+        pub fn set_check_total_supply(&mut self, enabled: bool) -> Result<()> {
+            self.ensure_owner()?;
+            self.check_total_supply = enabled;
+            Ok(())
+        }
 
-                let new_swap = (
-                    caller,
-                    token_a,
-                    token_b,
-                    amount_a,
-                    amount_b,
-                    expiration,
-                    0,
-                    0,
-                    allowed_acceptor,
-                );
+        #[ink(message)]
+        pub fn set_oracle(&mut self, oracle: Option<AccountId>) -> Result<()> {
+            self.ensure_owner()?;
+            self.oracle = oracle;
+            Ok(())
+        }
 
-                self.swaps.insert(self.swap_count, &new_swap);
-                let id = self.swap_count;
-                self.swap_count = self.swap_count.checked_add(1).ok_or(Error::CallFailed)?;
+        #[ink(message)]
+        pub fn set_max_deviation_bps(&mut self, bps: u32) -> Result<()> {
+            self.ensure_owner()?;
+            self.max_deviation_bps = bps;
+            Ok(())
+        }
 
-                self.env().emit_event(SwapCreated {
-                    id,
-                    creator: caller,
-                });
+        #[ink(message)]
+        pub fn set_native_wrapper(&mut self, wrapper: Option<AccountId>) -> Result<()> {
+            self.ensure_owner()?;
+            self.native_wrapper = wrapper;
+            Ok(())
+        }
 
-                Ok(id)
-            }
+        #[ink(message)]
+        pub fn set_max_open_per_token(&mut self, max: u32) -> Result<()> {
+            self.ensure_owner()?;
+            self.max_open_per_token = max;
+            Ok(())
         }
 
         #[ink(message)]
-        pub fn delete_swap(&mut self, swap_id: u64) -> Result<()> {
-            if !self.swaps.contains(&swap_id) {
-                return Err(Error::SwapNotFound);
-            }
+        pub fn set_creation_fee(&mut self, fee: Balance) -> Result<()> {
+            self.ensure_owner()?;
+            self.creation_fee = fee;
+            Ok(())
+        }
 
-            let swap_data = self.swaps.get(&swap_id).unwrap();
-            let creator = swap_data.0;
+        #[ink(message)]
+        pub fn withdraw_fees(&mut self, to: AccountId) -> Result<()> {
+            self.ensure_owner()?;
+            let amount = self.collected_fees;
+            self.collected_fees = 0;
+            self.env()
+                .transfer(to, amount)
+                .map_err(|_| Error::TransferFailed)
+        }
 
-            if self.env().caller() != creator {
-                return Err(Error::Unauthorized);
-            }
+        #[ink(message)]
+        pub fn set_call_gas_limit(&mut self, gas_limit: u64) -> Result<()> {
+            self.ensure_owner()?;
+            self.call_gas_limit = gas_limit;
+            Ok(())
+        }
 
-            self.swaps.remove(&swap_id);
+        // Bounds what a token can burn through a call_gas_limit of 0; a cap of 0 removes it.
+        #[ink(message)]
+        pub fn set_max_call_gas_limit(&mut self, cap: u64) -> Result<()> {
+            self.ensure_owner()?;
+            self.max_call_gas_limit = cap;
+            Ok(())
+        }
 
-            self.env().emit_event(SwapDeleted { id: swap_id });
+        #[ink(message)]
+        pub fn set_complete_if_below(&mut self, threshold: Balance) -> Result<()> {
+            self.ensure_owner()?;
+            self.complete_if_below = threshold;
+            Ok(())
+        }
 
+        #[ink(message)]
+        pub fn set_max_duration(&mut self, duration: BlockNumber) -> Result<()> {
+            self.ensure_owner()?;
+            self.max_duration = duration;
             Ok(())
         }
 
-        fn transfer_token(
+        #[ink(message)]
+        pub fn effective_xcall_gas(&self) -> u64 {
+            self.xcall_gas()
+        }
+
+        // Gas passed to every cross-contract helper below: call_gas_limit clamped to any
+        // max_call_gas_limit, where a call_gas_limit of 0 ("all remaining gas") becomes the cap.
+        fn xcall_gas(&self) -> u64 {
+            match (self.call_gas_limit, self.max_call_gas_limit) {
+                (gas_limit, 0) => gas_limit,
+                (0, cap) => cap,
+                (gas_limit, cap) => gas_limit.min(cap),
+            }
+        }
+
+        #[ink(message)]
+        pub fn supported_selectors(&self) -> Vec<[u8; 4]> {
+            ink::prelude::vec![
+                BALANCE_OF_SELECTOR,
+                TRANSFER_SELECTOR,
+                TOTAL_SUPPLY_SELECTOR,
+                TOKEN_SYMBOL_SELECTOR
+            ]
+        }
+
+        // Every cross-contract call goes through a cfg(not(test)) helper like this one; unit
+        // tests replace them with the in-memory stand-ins in tests::mock. Callers pass the gas
+        // in, so the stand-ins record exactly what would have been forwarded.
+        #[cfg(not(test))]
+        fn get_balance(
             &self,
             token_contract: AccountId,
-            from: AccountId,
-            to: AccountId,
-            amount: Balance,
-        ) -> Result<()> {
-            let transfer_result: core::result::Result<
-                core::result::Result<(), LangError>,
+            account: AccountId,
+            gas_limit: u64,
+        ) -> Result<Balance> {
+            let result: core::result::Result<
+                core::result::Result<Balance, LangError>,
                 ink_env::Error,
             > = build_call::<DefaultEnvironment>()
                 .call(token_contract)
-                .gas_limit(5000)
+                .gas_limit(gas_limit)
                 .transferred_value(0)
                 .exec_input(
-                    ExecutionInput::new(Selector::new(ink::selector_bytes!("transfer")))
-                        .push_arg(from)
-                        .push_arg(to)
-                        .push_arg(amount),
+                    ExecutionInput::new(Selector::new(BALANCE_OF_SELECTOR)).push_arg(account),
                 )
-                .returns::<()>()
+                .returns::<Balance>()
                 .try_invoke();
 
-            match transfer_result {
-                Ok(Ok(())) => Ok(()),
-                Ok(Err(_)) => Err(Error::TransferFailed),
+            match result {
+                Ok(Ok(balance)) => Ok(balance),
+                Ok(Err(_)) => Err(Error::InsufficientBalance),
                 Err(_) => Err(Error::CallFailed),
             }
         }
 
-        #[ink(message)]
-        pub fn accept_swap(
-            &mut self,
-            swap_id: u64,
-            amount_a: Balance,
-            amount_b: Balance,
-        ) -> Result<()> {
-            if !self.swaps.contains(&swap_id) {
-                return Err(Error::SwapNotFound);
-            }
-
-            let swap_data = self.swaps.get(&swap_id).unwrap();
-
-            let creator = swap_data.0;
-            let token_a = swap_data.1;
-            let token_b = swap_data.2;
-            let required_a = swap_data.3;
-            let required_b = swap_data.4;
-            let expiration = swap_data.5;
-            let accepted_a = swap_data.6;
-            let accepted_b = swap_data.7;
-
-            if let Some(allowed_acceptor) = swap_data.8 {
-                if self.env().caller() != allowed_acceptor {
-                    return Err(Error::Unauthorized);
-                }
-            }
+        #[cfg(not(test))]
+        fn get_total_supply(&self, token_contract: AccountId, gas_limit: u64) -> Result<Balance> {
+            let result: core::result::Result<
+                core::result::Result<Balance, LangError>,
+                ink_env::Error,
+            > = build_call::<DefaultEnvironment>()
+                .call(token_contract)
+                .gas_limit(gas_limit)
+                .transferred_value(0)
+                .exec_input(ExecutionInput::new(Selector::new(TOTAL_SUPPLY_SELECTOR)))
+                .returns::<Balance>()
+                .try_invoke();
 
-            if self.env().block_number() > expiration {
-                return Err(Error::SwapExpired);
+            match result {
+                Ok(Ok(total_supply)) => Ok(total_supply),
+                Ok(Err(_)) | Err(_) => Err(Error::CallFailed),
             }
+        }
 
-            if amount_a + accepted_a > required_a || amount_b + accepted_b > required_b {
-                return Err(Error::InsufficientBalance);
-            }
+        #[cfg(not(test))]
+        fn get_oracle_price(
+            &self,
+            oracle: AccountId,
+            token_a: AccountId,
+            token_b: AccountId,
+            gas_limit: u64,
+        ) -> Result<Balance> {
+            let result: core::result::Result<
+                core::result::Result<Balance, LangError>,
+                ink_env::Error,
+            > = build_call::<DefaultEnvironment>()
+                .call(oracle)
+                .gas_limit(gas_limit)
+                .transferred_value(0)
+                .exec_input(
+                    ExecutionInput::new(Selector::new(PRICE_SELECTOR))
+                        .push_arg(token_a)
+                        .push_arg(token_b),
+                )
+                .returns::<Balance>()
+                .try_invoke();
+
+            match result {
+                Ok(Ok(price)) => Ok(price),
+                Ok(Err(_)) | Err(_) => Err(Error::CallFailed),
+            }
+        }
+
+        // Rejects amounts whose implied price is more than max_bps away from the oracle
+        // price, which is returned otherwise.
+        fn check_price(
+            &self,
+            oracle: AccountId,
+            token_a: AccountId,
+            token_b: AccountId,
+            amount_a: Balance,
+            amount_b: Balance,
+            max_bps: u32,
+        ) -> Result<Balance> {
+            let oracle_price = self.get_oracle_price(oracle, token_a, token_b, self.xcall_gas())?;
+            if oracle_price == 0 {
+                return Err(Error::PriceOutOfBand);
+            }
+
+            let implied_price = amount_b
+                .checked_mul(PRICE_SCALE)
+                .ok_or(Error::Overflow)?
+                .checked_div(amount_a)
+                .ok_or(Error::InvalidAmount)?;
+            let deviation_bps = implied_price
+                .abs_diff(oracle_price)
+                .checked_mul(10_000)
+                .ok_or(Error::PriceOutOfBand)?
+                / oracle_price;
+
+            if deviation_bps > Balance::from(max_bps) {
+                return Err(Error::PriceOutOfBand);
+            }
+            Ok(oracle_price)
+        }
+
+        // Tokens without a symbol, or without the metadata selector, yield empty bytes.
+        fn get_symbol(&mut self, token_contract: AccountId) -> Vec<u8> {
+            if let Some(symbol) = self.symbols.get(token_contract) {
+                return symbol;
+            }
+
+            match self.fetch_symbol(token_contract, self.xcall_gas()) {
+                Some(symbol) => {
+                    self.symbols.insert(token_contract, &symbol);
+                    symbol
+                }
+                None => Vec::new(),
+            }
+        }
+
+        #[cfg(not(test))]
+        fn fetch_symbol(&self, token_contract: AccountId, gas_limit: u64) -> Option<Vec<u8>> {
+            let result: core::result::Result<
+                core::result::Result<Option<Vec<u8>>, LangError>,
+                ink_env::Error,
+            > = build_call::<DefaultEnvironment>()
+                .call(token_contract)
+                .gas_limit(gas_limit)
+                .transferred_value(0)
+                .exec_input(ExecutionInput::new(Selector::new(TOKEN_SYMBOL_SELECTOR)))
+                .returns::<Option<Vec<u8>>>()
+                .try_invoke();
+
+            match result {
+                Ok(Ok(symbol)) => symbol,
+                Ok(Err(_)) | Err(_) => None,
+            }
+        }
+
+        #[ink(message)]
+        pub fn pair_symbols(&mut self, swap_id: u64) -> Result<(Vec<u8>, Vec<u8>)> {
+            let swap_data = self.swaps.get(swap_id).ok_or(Error::SwapNotFound)?;
+            Ok((
+                self.get_symbol(swap_data.token_a),
+                self.get_symbol(swap_data.token_b),
+            ))
+        }
+
+        // Payable so that it can collect creation_fee: the transferred value must cover the
+        // fee and is kept in full as fees. Callers sending no value are unaffected while the
+        // fee is 0. With an oracle configured, `force` skips the price band check.
+        #[ink(message, payable)]
+        #[allow(clippy::too_many_arguments)]
+        pub fn create_swap(
+            &mut self,
+            token_a: AccountId,
+            token_b: AccountId,
+            amount_a: Balance,
+            amount_b: Balance,
+            duration: BlockNumber,
+            allowed_acceptor: Option<AccountId>, // Nouvel argument
+            force: bool,
+        ) -> Result<u64> {
+            self.ensure_not_paused()?;
+
+            if token_a == token_b {
+                return Err(Error::IdenticalTokens);
+            }
+
+            if amount_a == 0 || amount_b == 0 {
+                return Err(Error::ZeroAmount);
+            }
+
+            if duration > self.max_duration {
+                return Err(Error::DurationTooLong);
+            }
+
+            let paid = self.env().transferred_value();
+            if paid < self.creation_fee {
+                return Err(Error::InsufficientBalance);
+            }
+            self.collected_fees = self
+                .collected_fees
+                .checked_add(paid)
+                .ok_or(Error::Overflow)?;
+
+            if let Some(delegate) = self.delegated_contract {
+                self.delegate_create_swap(
+                    delegate,
+                    self.env().caller(),
+                    token_a,
+                    token_b,
+                    amount_a,
+                    amount_b,
+                    duration,
+                    allowed_acceptor,
+                    self.xcall_gas(),
+                )
+            } else {
+                let open_for_token = self.open_by_token.get(token_a).unwrap_or(0);
+                if self.max_open_per_token != 0 && open_for_token >= self.max_open_per_token {
+                    return Err(Error::TokenSwapLimitReached);
+                }
+
+                if self.check_total_supply
+                    && (amount_a > self.get_total_supply(token_a, self.xcall_gas())?
+                        || amount_b > self.get_total_supply(token_b, self.xcall_gas())?)
+                {
+                    return Err(Error::AmountExceedsSupply);
+                }
+
+                if let Some(oracle) = self.oracle {
+                    if !force {
+                        self.check_price(
+                            oracle,
+                            token_a,
+                            token_b,
+                            amount_a,
+                            amount_b,
+                            self.max_deviation_bps,
+                        )?;
+                    }
+                }
+
+                let caller = self.env().caller();
+                let balance_a: Balance = self.get_balance(token_a, caller, self.xcall_gas())?;
+
+                if balance_a < amount_a {
+                    return Err(Error::InsufficientBalance);
+                }
+
+                let balance_b: Balance = self.get_balance(token_b, caller, self.xcall_gas())?;
+                if balance_b < amount_b {
+                    return Err(Error::InsufficientBalance);
+                }
+
+                let contract = self.env().account_id();
+                let held_before = self.get_balance(token_a, contract, self.xcall_gas())?;
+                self.transfer_token(token_a, caller, contract, amount_a, self.xcall_gas())?;
+                let received = self
+                    .get_balance(token_a, contract, self.xcall_gas())?
+                    .saturating_sub(held_before);
+
+                let expiration = self
+                    .env()
+                    .block_number()
+                    .checked_add(duration)
+                    .ok_or(Error::Overflow)?;
+
+                let new_swap = Swap {
+                    creator: caller,
+                    token_a,
+                    token_b,
+                    required_a: amount_a,
+                    required_b: amount_b,
+                    expiration,
+                    accepted_a: 0,
+                    accepted_b: 0,
+                    allowed_acceptor,
+                };
+
+                self.swaps.insert(self.swap_count, &new_swap);
+                self.target_ratios
+                    .insert(self.swap_count, &(amount_a, amount_b));
+                self.escrowed_a.insert(self.swap_count, &received);
+                let id = self.swap_count;
+                self.swap_count = self.swap_count.checked_add(1).ok_or(Error::Overflow)?;
+                self.active_swaps = self.active_swaps.checked_add(1).ok_or(Error::Overflow)?;
+
+                let open = self.open_by_creator.get(caller).unwrap_or(0);
+                self.open_by_creator
+                    .insert(caller, &open.checked_add(1).ok_or(Error::Overflow)?);
+                self.open_by_token.insert(
+                    token_a,
+                    &open_for_token.checked_add(1).ok_or(Error::Overflow)?,
+                );
+                let open_for_token_b = self.open_by_token_b.get(token_b).unwrap_or(0);
+                self.open_by_token_b.insert(
+                    token_b,
+                    &open_for_token_b.checked_add(1).ok_or(Error::Overflow)?,
+                );
+
+                self.env().emit_event(SwapCreated {
+                    id,
+                    creator: caller,
+                });
+
+                Ok(id)
+            }
+        }
+
+        // create_swap_delegate(creator, token_a, token_b, amount_a, amount_b, duration,
+        // allowed_acceptor) -> u64, returning the id the delegate assigned. The delegate
+        // sees this contract as its caller, so the creator is passed explicitly.
+        #[cfg(not(test))]
+        #[allow(clippy::too_many_arguments)]
+        fn delegate_create_swap(
+            &self,
+            delegate: AccountId,
+            creator: AccountId,
+            token_a: AccountId,
+            token_b: AccountId,
+            amount_a: Balance,
+            amount_b: Balance,
+            duration: BlockNumber,
+            allowed_acceptor: Option<AccountId>,
+            gas_limit: u64,
+        ) -> Result<u64> {
+            let selector = ink::selector_bytes!("create_swap_delegate");
+            let nested_result: core::result::Result<
+                core::result::Result<u64, LangError>,
+                ink_env::Error,
+            > = build_call::<DefaultEnvironment>()
+                .call(delegate)
+                .gas_limit(gas_limit)
+                .transferred_value(0)
+                .exec_input(
+                    ExecutionInput::new(Selector::new(selector))
+                        .push_arg(creator)
+                        .push_arg(token_a)
+                        .push_arg(token_b)
+                        .push_arg(amount_a)
+                        .push_arg(amount_b)
+                        .push_arg(duration)
+                        .push_arg(allowed_acceptor),
+                )
+                .returns::<u64>()
+                .try_invoke();
 
-            self.transfer_token(token_a, self.env().caller(), creator, amount_a)?;
-            self.transfer_token(token_b, self.env().caller(), creator, amount_b)?;
+            match nested_result {
+                Ok(inner_result) => inner_result.map_err(|_| Error::DelegateFunctionFailed),
+                Err(_) => Err(Error::DelegateFailed),
+            }
+        }
 
-            let allowed_acceptor = swap_data.8;
+        // Like create_swap, but stores and returns the token A amount actually received,
+        // which is lower than amount_a for fee-on-transfer tokens. Payable for creation_fee.
+        #[ink(message, payable)]
+        #[allow(clippy::too_many_arguments)]
+        pub fn safe_create_swap(
+            &mut self,
+            token_a: AccountId,
+            token_b: AccountId,
+            amount_a: Balance,
+            amount_b: Balance,
+            duration: BlockNumber,
+            allowed_acceptor: Option<AccountId>,
+            force: bool,
+        ) -> Result<(u64, Balance)> {
+            if self.delegated_contract.is_some() {
+                return Err(Error::DelegationActive);
+            }
 
-            let updated_swap = (
-                creator,
+            let id = self.create_swap(
                 token_a,
                 token_b,
-                required_a,
-                required_b,
-                expiration,
-                accepted_a + amount_a,
-                accepted_b + amount_b,
+                amount_a,
+                amount_b,
+                duration,
                 allowed_acceptor,
-            );
+                force,
+            )?;
 
-            self.swaps.insert(swap_id, &updated_swap);
+            let escrowed = self.escrowed_a.get(id).unwrap_or(0);
+            if escrowed == 0 {
+                return Err(Error::TransferFailed);
+            }
 
-            self.env().emit_event(SwapAccepted {
+            let mut swap_data = self.swaps.get(id).ok_or(Error::SwapNotFound)?;
+            swap_data.required_a = escrowed;
+            self.swaps.insert(id, &swap_data);
+            self.target_ratios
+                .insert(id, &(escrowed, swap_data.required_b));
+
+            Ok((id, escrowed))
+        }
+
+        #[ink(message)]
+        pub fn delete_swap(&mut self, swap_id: u64) -> Result<()> {
+            self.cancel_and_refund(swap_id)
+        }
+
+        // Returns the unfilled token A escrow to the creator and removes the swap.
+        #[ink(message)]
+        pub fn cancel_and_refund(&mut self, swap_id: u64) -> Result<()> {
+            self.cancel(swap_id, self.env().caller())
+        }
+
+        #[ink(message)]
+        pub fn delete_swap_to(&mut self, swap_id: u64, refund_to: AccountId) -> Result<()> {
+            if refund_to == AccountId::from([0u8; 32]) {
+                return Err(Error::ZeroAddress);
+            }
+
+            self.cancel(swap_id, refund_to)
+        }
+
+        // Callable by anyone once the swap has expired; the refund always goes to the creator.
+        #[ink(message)]
+        pub fn reap_expired(&mut self, swap_id: u64) -> Result<()> {
+            self.ensure_not_frozen()?;
+
+            let swap_data = self.swaps.get(swap_id).ok_or(Error::SwapNotFound)?;
+
+            if self.env().block_number() <= swap_data.expiration {
+                return Err(Error::SwapNotExpired);
+            }
+
+            let refund_a = self.refundable_a(swap_id, &swap_data);
+            self.remove_swap(swap_id);
+
+            if refund_a > 0 {
+                self.transfer_token(
+                    swap_data.token_a,
+                    self.env().account_id(),
+                    swap_data.creator,
+                    refund_a,
+                    self.xcall_gas(),
+                )?;
+            }
+
+            self.env().emit_event(SwapExpiredReaped {
                 id: swap_id,
-                acceptor: self.env().caller(),
+                reaper: self.env().caller(),
             });
 
             Ok(())
         }
+
+        fn cancel(&mut self, swap_id: u64, refund_to: AccountId) -> Result<()> {
+            self.ensure_not_frozen()?;
+
+            if !self.swaps.contains(swap_id) {
+                return Err(Error::SwapNotFound);
+            }
+
+            let swap_data = self.swaps.get(swap_id).unwrap();
+            let creator = swap_data.creator;
+
+            if self.env().caller() != creator {
+                return Err(Error::Unauthorized);
+            }
+
+            let refund_a = self.refundable_a(swap_id, &swap_data);
+            self.remove_swap(swap_id);
+
+            if refund_a > 0 {
+                self.transfer_token(
+                    swap_data.token_a,
+                    self.env().account_id(),
+                    refund_to,
+                    refund_a,
+                    self.xcall_gas(),
+                )?;
+            }
+
+            self.env().emit_event(SwapDeleted { id: swap_id });
+
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn set_allowed_acceptor(
+            &mut self,
+            swap_id: u64,
+            acceptor: Option<AccountId>,
+        ) -> Result<()> {
+            self.ensure_not_frozen()?;
+
+            let mut swap_data = self.swaps.get(swap_id).ok_or(Error::SwapNotFound)?;
+
+            if self.env().caller() != swap_data.creator {
+                return Err(Error::Unauthorized);
+            }
+
+            swap_data.allowed_acceptor = acceptor;
+            self.swaps.insert(swap_id, &swap_data);
+
+            self.env().emit_event(AcceptorUpdated { id: swap_id });
+
+            Ok(())
+        }
+
+        #[ink(message)]
+        pub fn set_note(&mut self, swap_id: u64, note: Option<Vec<u8>>) -> Result<()> {
+            self.ensure_not_frozen()?;
+
+            let swap_data = self.swaps.get(swap_id).ok_or(Error::SwapNotFound)?;
+
+            if self.env().caller() != swap_data.creator {
+                return Err(Error::Unauthorized);
+            }
+
+            match note {
+                Some(note) if note.len() > MAX_NOTE_LEN => return Err(Error::NoteTooLong),
+                Some(note) => {
+                    self.notes.insert(swap_id, &note);
+                }
+                None => self.notes.remove(swap_id),
+            }
+
+            Ok(())
+        }
+
+        // Readable by the creator and, for restricted swaps, the allowed acceptor.
+        #[ink(message)]
+        pub fn get_note(&self, swap_id: u64) -> Result<Option<Vec<u8>>> {
+            let swap_data = self.swaps.get(swap_id).ok_or(Error::SwapNotFound)?;
+
+            let caller = self.env().caller();
+            if caller != swap_data.creator && swap_data.allowed_acceptor != Some(caller) {
+                return Err(Error::Unauthorized);
+            }
+
+            Ok(self.notes.get(swap_id))
+        }
+
+        // Only takes effect when the swap's allowed acceptor is a contract.
+        #[ink(message)]
+        pub fn set_acceptor_callback(&mut self, swap_id: u64, enabled: bool) -> Result<()> {
+            self.ensure_not_frozen()?;
+
+            let swap_data = self.swaps.get(swap_id).ok_or(Error::SwapNotFound)?;
+
+            if self.env().caller() != swap_data.creator {
+                return Err(Error::Unauthorized);
+            }
+
+            if enabled {
+                self.acceptor_callbacks.insert(swap_id, &true);
+            } else {
+                self.acceptor_callbacks.remove(swap_id);
+            }
+
+            Ok(())
+        }
+
+        // Zero on both sides clears the minimum. Fixed once the swap has taken its first fill,
+        // so the creator cannot change the terms under acceptors already part way through it.
+        #[ink(message)]
+        pub fn set_min_fill(&mut self, swap_id: u64, min_a: Balance, min_b: Balance) -> Result<()> {
+            self.ensure_not_frozen()?;
+
+            let swap_data = self.swaps.get(swap_id).ok_or(Error::SwapNotFound)?;
+
+            if self.env().caller() != swap_data.creator {
+                return Err(Error::Unauthorized);
+            }
+
+            if swap_data.accepted_a > 0 {
+                return Err(Error::SwapAlreadyFilled);
+            }
+
+            if min_a == 0 && min_b == 0 {
+                self.min_fills.remove(swap_id);
+            } else {
+                self.min_fills.insert(swap_id, &(min_a, min_b));
+            }
+
+            Ok(())
+        }
+
+        // Lets accept_swap_at_oracle_rate settle this swap while the oracle rate is within
+        // band_bps of the listed price; None turns it off again.
+        #[ink(message)]
+        pub fn set_oracle_settlement(&mut self, swap_id: u64, band_bps: Option<u32>) -> Result<()> {
+            self.ensure_not_frozen()?;
+
+            let swap_data = self.swaps.get(swap_id).ok_or(Error::SwapNotFound)?;
+
+            if self.env().caller() != swap_data.creator {
+                return Err(Error::Unauthorized);
+            }
+
+            if let Some(band_bps) = band_bps {
+                self.oracle_bands.insert(swap_id, &band_bps);
+            } else {
+                self.oracle_bands.remove(swap_id);
+            }
+
+            Ok(())
+        }
+
+        #[cfg(not(test))]
+        fn notify_acceptor(
+            &self,
+            acceptor: AccountId,
+            swap_id: u64,
+            amount_a: Balance,
+            amount_b: Balance,
+            gas_limit: u64,
+        ) -> Result<()> {
+            let callback_result: core::result::Result<
+                core::result::Result<(), LangError>,
+                ink_env::Error,
+            > = build_call::<DefaultEnvironment>()
+                .call(acceptor)
+                .gas_limit(gas_limit)
+                .transferred_value(0)
+                .exec_input(
+                    ExecutionInput::new(Selector::new(ON_SWAP_ACCEPTED_SELECTOR))
+                        .push_arg(swap_id)
+                        .push_arg(amount_a)
+                        .push_arg(amount_b),
+                )
+                .returns::<()>()
+                .try_invoke();
+
+            match callback_result {
+                Ok(Ok(())) => Ok(()),
+                Ok(Err(_)) | Err(_) => Err(Error::CallbackFailed),
+            }
+        }
+
+        // Unfilled token A, capped by what this swap actually escrowed so that a
+        // fee-on-transfer shortfall is never made up from other swaps' escrow.
+        fn refundable_a(&self, swap_id: u64, swap_data: &Swap) -> Balance {
+            self.escrow_cap(swap_id, swap_data)
+                .saturating_sub(swap_data.accepted_a)
+        }
+
+        // Most token A the swap can ever pay out: required_a, or less if escrow arrived short.
+        fn escrow_cap(&self, swap_id: u64, swap_data: &Swap) -> Balance {
+            self.escrowed_a
+                .get(swap_id)
+                .map_or(swap_data.required_a, |escrowed| {
+                    escrowed.min(swap_data.required_a)
+                })
+        }
+
+        #[cfg(not(test))]
+        fn transfer_token(
+            &self,
+            token_contract: AccountId,
+            from: AccountId,
+            to: AccountId,
+            amount: Balance,
+            gas_limit: u64,
+        ) -> Result<()> {
+            let transfer_result: core::result::Result<
+                core::result::Result<(), LangError>,
+                ink_env::Error,
+            > = build_call::<DefaultEnvironment>()
+                .call(token_contract)
+                .gas_limit(gas_limit)
+                .transferred_value(0)
+                .exec_input(
+                    ExecutionInput::new(Selector::new(TRANSFER_SELECTOR))
+                        .push_arg(from)
+                        .push_arg(to)
+                        .push_arg(amount),
+                )
+                .returns::<()>()
+                .try_invoke();
+
+            match transfer_result {
+                Ok(Ok(())) => Ok(()),
+                Ok(Err(_)) => Err(Error::TransferFailed),
+                Err(_) => Err(Error::CallFailed),
+            }
+        }
+
+        #[ink(message)]
+        pub fn accept_swap(
+            &mut self,
+            swap_id: u64,
+            amount_a: Balance,
+            amount_b: Balance,
+        ) -> Result<()> {
+            let caller = self.env().caller();
+            self.non_reentrant(|this| this.fill(swap_id, amount_a, amount_b, caller, None))
+        }
+
+        // (swap_id, amount_a, amount_b) per entry; any failing fill reverts the whole batch.
+        #[ink(message)]
+        pub fn accept_swaps(&mut self, fills: Vec<(u64, Balance, Balance)>) -> Result<()> {
+            if fills.len() > MAX_BATCH {
+                return Err(Error::BatchTooLarge);
+            }
+
+            // Every entry is checked before the first fill, so an invalid one fails the batch
+            // with nothing written or transferred. Entries that only conflict with each other
+            // (say, overfilling the same swap) fail inside fill, where the message revert undoes
+            // the earlier fills.
+            let caller = self.env().caller();
+            for &(swap_id, amount_a, amount_b) in &fills {
+                let swap_data = self.open_swap(swap_id)?;
+                self.validate_fill(
+                    swap_id, &swap_data, caller, caller, amount_a, amount_b, None,
+                )?;
+            }
+
+            self.non_reentrant(|this| {
+                for (swap_id, amount_a, amount_b) in fills {
+                    this.fill(swap_id, amount_a, amount_b, caller, None)?;
+                }
+                Ok(())
+            })
+        }
+
+        // Pays the token B leg in native currency, wrapped through native_wrapper. The
+        // swap's token_b must be the wrapper and the transferred value must equal amount_b.
+        #[ink(message, payable)]
+        pub fn accept_swap_with_native(
+            &mut self,
+            swap_id: u64,
+            amount_a: Balance,
+            amount_b: Balance,
+        ) -> Result<()> {
+            self.non_reentrant(|this| this.fill_with_native(swap_id, amount_a, amount_b))
+        }
+
+        fn fill_with_native(
+            &mut self,
+            swap_id: u64,
+            amount_a: Balance,
+            amount_b: Balance,
+        ) -> Result<()> {
+            let wrapper = self.native_wrapper.ok_or(Error::NoNativeWrapper)?;
+            let swap_data = self.open_swap(swap_id)?;
+
+            if swap_data.token_b != wrapper {
+                return Err(Error::NoNativeWrapper);
+            }
+
+            if self.env().transferred_value() != amount_b {
+                return Err(Error::InvalidAmount);
+            }
+
+            self.deposit_native(wrapper, amount_b, self.xcall_gas())?;
+
+            self.fill(swap_id, amount_a, amount_b, self.env().account_id(), None)
+        }
+
+        #[cfg(not(test))]
+        fn deposit_native(
+            &self,
+            wrapper: AccountId,
+            amount: Balance,
+            gas_limit: u64,
+        ) -> Result<()> {
+            let deposit_result: core::result::Result<
+                core::result::Result<(), LangError>,
+                ink_env::Error,
+            > = build_call::<DefaultEnvironment>()
+                .call(wrapper)
+                .gas_limit(gas_limit)
+                .transferred_value(amount)
+                .exec_input(ExecutionInput::new(Selector::new(DEPOSIT_SELECTOR)))
+                .returns::<()>()
+                .try_invoke();
+
+            match deposit_result {
+                Ok(Ok(())) => Ok(()),
+                Ok(Err(_)) => Err(Error::TransferFailed),
+                Err(_) => Err(Error::CallFailed),
+            }
+        }
+
+        // Runs every accept_swap check for `who` without transferring anything.
+        #[ink(message)]
+        pub fn accept_precheck(
+            &self,
+            swap_id: u64,
+            who: AccountId,
+            amount_a: Balance,
+            amount_b: Balance,
+        ) -> Result<()> {
+            let swap_data = self.open_swap(swap_id)?;
+            self.validate_fill(swap_id, &swap_data, who, who, amount_a, amount_b, None)
+                .map(|_| ())
+        }
+
+        // Returns the accepted amounts after the fill. With oracle_price set, the fill is priced
+        // at that rate (token B per PRICE_SCALE of token A) instead of the listed ratio.
+        #[allow(clippy::too_many_arguments)]
+        fn validate_fill(
+            &self,
+            swap_id: u64,
+            swap_data: &Swap,
+            acceptor: AccountId,
+            payer_b: AccountId,
+            amount_a: Balance,
+            amount_b: Balance,
+            oracle_price: Option<Balance>,
+        ) -> Result<(Balance, Balance)> {
+            self.ensure_not_paused()?;
+
+            if swap_data.required_a == 0 || swap_data.required_b == 0 {
+                return Err(Error::InvalidAmount);
+            }
+
+            // Token B paid for no token A would use up the swap's B capacity and strand its A.
+            if amount_a == 0 {
+                return Err(Error::ZeroAmount);
+            }
+
+            if let Some(allowed_acceptor) = swap_data.allowed_acceptor {
+                if acceptor != allowed_acceptor {
+                    return Err(Error::Unauthorized);
+                }
+            }
+
+            if !self.allow_self_accept && acceptor == swap_data.creator {
+                return Err(Error::SelfAccept);
+            }
+
+            if self.env().block_number() > swap_data.expiration {
+                return Err(Error::SwapExpired);
+            }
+
+            let new_accepted_a = swap_data
+                .accepted_a
+                .checked_add(amount_a)
+                .ok_or(Error::Overflow)?;
+            let new_accepted_b = swap_data
+                .accepted_b
+                .checked_add(amount_b)
+                .ok_or(Error::Overflow)?;
+            if new_accepted_a > self.escrow_cap(swap_id, swap_data)
+                || new_accepted_b > swap_data.required_b
+            {
+                return Err(Error::InsufficientBalance);
+            }
+
+            // amount_b must pay at least the price for amount_a, otherwise the escrowed
+            // token A could be taken for little or no token B. At the listed ratio it is enough
+            // for the running totals to stay at that price, so the fill paying exactly the
+            // remaining token B passes even after earlier fills rounded in the creator's favour.
+            let priced = match oracle_price {
+                Some(price) => widening_mul(amount_b, PRICE_SCALE) >= widening_mul(amount_a, price),
+                None => {
+                    let (required_a, required_b) = (swap_data.required_a, swap_data.required_b);
+                    widening_mul(amount_b, required_a) >= widening_mul(amount_a, required_b)
+                        || widening_mul(new_accepted_b, required_a)
+                            >= widening_mul(new_accepted_a, required_b)
+                }
+            };
+            if !priced {
+                return Err(Error::UnderPriced);
+            }
+
+            // A swap completes once all of its escrowed token A is paid out. At the listed
+            // ratio that also fills token B, unless a fee-on-transfer token escrowed short or
+            // the fill was at the oracle rate.
+            let escrow_cap = self.escrow_cap(swap_id, swap_data);
+            let completes = new_accepted_a == escrow_cap;
+
+            // No fill may leave an unfillable dust remainder behind.
+            let is_dust = |remaining: Balance| remaining > 0 && remaining < self.complete_if_below;
+            if !completes
+                && (is_dust(escrow_cap - new_accepted_a)
+                    || is_dust(swap_data.required_b - new_accepted_b))
+            {
+                return Err(Error::MustFillEntirely);
+            }
+
+            if let Some((min_a, min_b)) = self.min_fills.get(swap_id) {
+                if !completes && (amount_a < min_a || amount_b < min_b) {
+                    return Err(Error::FillTooSmall);
+                }
+            }
+
+            if self.get_balance(swap_data.token_b, payer_b, self.xcall_gas())? < amount_b {
+                return Err(Error::InsufficientBalance);
+            }
+
+            Ok((new_accepted_a, new_accepted_b))
+        }
+
+        // payer_b is the account token B is pulled from: the caller, or this contract when
+        // the caller paid in wrapped native currency.
+        fn fill(
+            &mut self,
+            swap_id: u64,
+            amount_a: Balance,
+            amount_b: Balance,
+            payer_b: AccountId,
+            oracle_price: Option<Balance>,
+        ) -> Result<()> {
+            let swap_data = self.open_swap(swap_id)?;
+
+            let creator = swap_data.creator;
+            let token_a = swap_data.token_a;
+            let token_b = swap_data.token_b;
+            let required_b = swap_data.required_b;
+            let escrow_cap = self.escrow_cap(swap_id, &swap_data);
+
+            let (new_accepted_a, new_accepted_b) = self.validate_fill(
+                swap_id,
+                &swap_data,
+                self.env().caller(),
+                payer_b,
+                amount_a,
+                amount_b,
+                oracle_price,
+            )?;
+
+            let allowed_acceptor = swap_data.allowed_acceptor;
+            let notify_acceptor = self.acceptor_callbacks.get(swap_id).unwrap_or(false);
+
+            let updated_swap = Swap {
+                accepted_a: new_accepted_a,
+                accepted_b: new_accepted_b,
+                ..swap_data
+            };
+
+            let completed = updated_swap.accepted_a == escrow_cap;
+            if completed {
+                let target_ratio = self.target_ratios.get(swap_id);
+                self.remove_swap(swap_id);
+                if let Some(target_ratio) = target_ratio {
+                    self.target_ratios.insert(swap_id, &target_ratio);
+                }
+                self.filled_totals
+                    .insert(swap_id, &(new_accepted_a, new_accepted_b));
+            } else {
+                self.swaps.insert(swap_id, &updated_swap);
+            }
+
+            let caller = self.env().caller();
+            let (filled_a, filled_b) = self.fills.get((caller, swap_id)).unwrap_or((0, 0));
+            self.fills.insert(
+                (caller, swap_id),
+                &(
+                    filled_a.checked_add(amount_a).ok_or(Error::Overflow)?,
+                    filled_b.checked_add(amount_b).ok_or(Error::Overflow)?,
+                ),
+            );
+
+            self.total_fills = self.total_fills.checked_add(1).ok_or(Error::Overflow)?;
+
+            if completed {
+                let count = self.completed_by_creator.get(creator).unwrap_or(0);
+                self.completed_by_creator
+                    .insert(creator, &count.checked_add(1).ok_or(Error::Overflow)?);
+            }
+
+            // The acceptor contract hears about the fill before any token moves, and can
+            // veto it by failing.
+            if let Some(acceptor) = allowed_acceptor {
+                if notify_acceptor && self.env().is_contract(&acceptor) {
+                    self.notify_acceptor(acceptor, swap_id, amount_a, amount_b, self.xcall_gas())?;
+                }
+            }
+
+            // State is written before calling out to the tokens; an Err from either
+            // transfer reverts the whole message, storage writes included. The escrowed
+            // token A is paid out first so token B is never taken without the payout.
+            self.transfer_token(
+                token_a,
+                self.env().account_id(),
+                caller,
+                amount_a,
+                self.xcall_gas(),
+            )?;
+            self.transfer_token(token_b, payer_b, creator, amount_b, self.xcall_gas())?;
+
+            self.env().emit_event(SwapAccepted {
+                id: swap_id,
+                acceptor: self.env().caller(),
+                amount_a,
+                amount_b,
+                accepted_a: updated_swap.accepted_a,
+                accepted_b: updated_swap.accepted_b,
+                completed,
+                remaining_a: escrow_cap - updated_swap.accepted_a,
+                remaining_b: required_b - updated_swap.accepted_b,
+            });
+
+            if completed {
+                self.env().emit_event(SwapFullyFilled {
+                    id: swap_id,
+                    last_acceptor: caller,
+                });
+            }
+
+            Ok(())
+        }
+
+        // Fills amount_b and the proportional amount_a (rounded down), returning amount_a.
+        #[ink(message)]
+        pub fn accept_swap_by_b(&mut self, swap_id: u64, amount_b: Balance) -> Result<Balance> {
+            let swap_data = self.open_swap(swap_id)?;
+            if swap_data.required_a == 0 || swap_data.required_b == 0 {
+                return Err(Error::InvalidAmount);
+            }
+
+            let (amount_a, _) = mul_div(amount_b, swap_data.required_a, swap_data.required_b)
+                .ok_or(Error::Overflow)?;
+            if amount_a == 0 {
+                return Err(Error::ZeroAmount);
+            }
+
+            self.accept_swap(swap_id, amount_a, amount_b)?;
+
+            Ok(amount_a)
+        }
+
+        // Settles amount_b at the live oracle rate rather than the listed ratio, on swaps whose
+        // creator opted in with set_oracle_settlement and only while the listed price is within
+        // the creator's band of that rate. Both legs stay bounded by the listed amounts.
+        // Returns the amount_a paid out (rounded down).
+        #[ink(message)]
+        pub fn accept_swap_at_oracle_rate(
+            &mut self,
+            swap_id: u64,
+            amount_b: Balance,
+        ) -> Result<Balance> {
+            let oracle = self.oracle.ok_or(Error::NoOracle)?;
+            let swap_data = self.open_swap(swap_id)?;
+            let band_bps = self
+                .oracle_bands
+                .get(swap_id)
+                .ok_or(Error::OracleSettlementDisabled)?;
+
+            let oracle_price = self.check_price(
+                oracle,
+                swap_data.token_a,
+                swap_data.token_b,
+                swap_data.required_a,
+                swap_data.required_b,
+                band_bps,
+            )?;
+            let amount_a = amount_b.checked_mul(PRICE_SCALE).ok_or(Error::Overflow)? / oracle_price;
+
+            let caller = self.env().caller();
+            self.non_reentrant(|this| {
+                this.fill(swap_id, amount_a, amount_b, caller, Some(oracle_price))
+            })?;
+
+            Ok(amount_a)
+        }
+
+        // Fills amount_a for the proportional amount_b (rounded up), returning amount_b.
+        #[ink(message)]
+        pub fn accept_swap_by_a(&mut self, swap_id: u64, amount_a: Balance) -> Result<Balance> {
+            let swap_data = self.open_swap(swap_id)?;
+            if swap_data.required_a == 0 || swap_data.required_b == 0 {
+                return Err(Error::InvalidAmount);
+            }
+
+            if amount_a > self.refundable_a(swap_id, &swap_data) {
+                return Err(Error::InsufficientBalance);
+            }
+
+            let product = amount_a
+                .checked_mul(swap_data.required_b)
+                .ok_or(Error::Overflow)?;
+            let quotient = product
+                .checked_div(swap_data.required_a)
+                .ok_or(Error::Overflow)?;
+            let amount_b = if product % swap_data.required_a == 0 {
+                quotient
+            } else {
+                quotient.checked_add(1).ok_or(Error::Overflow)?
+            };
+
+            self.accept_swap(swap_id, amount_a, amount_b)?;
+
+            Ok(amount_b)
+        }
+
+        #[ink(message)]
+        pub fn get_swap(&self, swap_id: u64) -> Result<Swap> {
+            self.swaps.get(swap_id).ok_or(Error::SwapNotFound)
+        }
+
+        #[ink(message)]
+        pub fn active_swap_count(&self) -> u64 {
+            self.active_swaps
+        }
+
+        #[ink(message)]
+        pub fn swap_exists(&self, swap_id: u64) -> bool {
+            self.swaps.contains(swap_id)
+        }
+
+        #[ink(message)]
+        pub fn total_fills(&self) -> u64 {
+            self.total_fills
+        }
+
+        #[ink(message)]
+        pub fn completed_count(&self, who: AccountId) -> u64 {
+            self.completed_by_creator.get(who).unwrap_or(0)
+        }
+
+        // Only the newest MAX_SCAN ids are walked, so positions in older swaps are not
+        // listed; read those through get_swaps with the known ids.
+        #[ink(message)]
+        pub fn my_open_fills(&self, me: AccountId) -> Vec<u64> {
+            self.scan_range()
+                .filter(|id| self.fills.contains((me, *id)))
+                .filter(|id| {
+                    self.swaps
+                        .get(id)
+                        .is_some_and(|swap| self.is_live(*id, &swap))
+                })
+                .collect()
+        }
+
+        // Cumulative (amount_a, amount_b) over all fills; their ratio is the realized price.
+        #[ink(message)]
+        pub fn average_price(&self, swap_id: u64) -> Result<(Balance, Balance)> {
+            match self.swaps.get(swap_id) {
+                Some(swap_data) => Ok((swap_data.accepted_a, swap_data.accepted_b)),
+                None => self.filled_totals.get(swap_id).ok_or(Error::SwapNotFound),
+            }
+        }
+
+        // Amounts of each side still open to fill.
+        #[ink(message)]
+        pub fn remaining_amounts(&self, swap_id: u64) -> Result<(Balance, Balance)> {
+            let swap_data = self.swaps.get(swap_id).ok_or(Error::SwapNotFound)?;
+            Ok((
+                self.refundable_a(swap_id, &swap_data),
+                swap_data.required_b.saturating_sub(swap_data.accepted_b),
+            ))
+        }
+
+        // Ids past MAX_SWAPS_PER_QUERY are ignored.
+        #[ink(message)]
+        pub fn get_swaps(&self, ids: Vec<u64>) -> Vec<Option<Swap>> {
+            ids.into_iter()
+                .take(MAX_SWAPS_PER_QUERY)
+                .map(|id| self.swaps.get(id))
+                .collect()
+        }
+
+        #[ink(message)]
+        pub fn target_ratio(&self, swap_id: u64) -> Result<(Balance, Balance)> {
+            self.target_ratios.get(swap_id).ok_or(Error::SwapNotFound)
+        }
+
+        // Token B received by the creator so far.
+        #[ink(message)]
+        pub fn proceeds(&self, swap_id: u64) -> Result<Balance> {
+            self.average_price(swap_id)
+                .map(|(_, accepted_b)| accepted_b)
+        }
+
+        #[ink(message)]
+        pub fn reserved_for_me_count(&self, who: AccountId) -> u64 {
+            self.scan_range()
+                .filter_map(|id| self.swaps.get(id).map(|swap| (id, swap)))
+                .filter(|(id, swap)| swap.allowed_acceptor == Some(who) && self.is_live(*id, swap))
+                .count() as u64
+        }
+
+        // Worst-case number of swap reads for a full scan over all issued ids.
+        #[ink(message)]
+        pub fn scan_cost_hint(&self) -> u64 {
+            self.swap_count
+        }
+
+        // True while any stored swap has `token` on either side, expired swaps included
+        // until they are reaped or cancelled.
+        #[ink(message)]
+        pub fn token_in_use(&self, token: AccountId) -> bool {
+            self.open_by_token.get(token).unwrap_or(0) > 0
+                || self.open_by_token_b.get(token).unwrap_or(0) > 0
+        }
+
+        // Distinct token_a contracts still holding escrow, expired swaps included.
+        #[ink(message)]
+        pub fn escrowed_token_count(&self) -> u32 {
+            let mut tokens: Vec<AccountId> = Vec::new();
+            for swap in self.scan_range().filter_map(|id| self.swaps.get(id)) {
+                if swap.accepted_a < swap.required_a && !tokens.contains(&swap.token_a) {
+                    tokens.push(swap.token_a);
+                }
+            }
+            tokens.len() as u32
+        }
+
+        // Bucket i counts live swaps expiring within [i, i + 1) * bucket_size blocks from
+        // now; swaps expiring past the last bucket are not counted.
+        #[ink(message)]
+        pub fn expiry_histogram(&self, bucket_size: BlockNumber, buckets: u32) -> Vec<u32> {
+            let buckets = buckets.min(MAX_HISTOGRAM_BUCKETS);
+            if bucket_size == 0 {
+                return Vec::new();
+            }
+
+            let now = self.env().block_number();
+            let mut histogram = ink::prelude::vec![0u32; buckets as usize];
+            for id in self.scan_range() {
+                let Some(swap) = self.swaps.get(id) else {
+                    continue;
+                };
+                if !self.is_live(id, &swap) {
+                    continue;
+                }
+                let bucket = ((swap.expiration - now) / bucket_size) as usize;
+                if let Some(count) = histogram.get_mut(bucket) {
+                    *count += 1;
+                }
+            }
+            histogram
+        }
+
+        // The id the next locally created swap will be assigned.
+        #[ink(message)]
+        pub fn next_swap_id(&self) -> u64 {
+            self.swap_count
+        }
+
+        // Accounts past MAX_ACCOUNTS_PER_QUERY are ignored.
+        #[ink(message)]
+        pub fn open_counts(&self, accounts: Vec<AccountId>) -> Vec<u64> {
+            accounts
+                .into_iter()
+                .take(MAX_ACCOUNTS_PER_QUERY)
+                .map(|account| self.open_by_creator.get(account).unwrap_or(0))
+                .collect()
+        }
+
+        // Completed swaps are removed, so every active swap still has token A in escrow;
+        // expired swaps count until they are reaped or cancelled.
+        #[ink(message)]
+        pub fn has_escrow(&self) -> bool {
+            self.active_swaps > 0
+        }
+
+        // Newest first, at most MAX_SWAPS_PER_QUERY entries.
+        #[ink(message)]
+        pub fn latest_swaps(&self, limit: u64) -> Vec<(u64, Swap)> {
+            // Capped while still a u64, since `as usize` truncates on wasm32.
+            let limit = limit.min(MAX_SWAPS_PER_QUERY as u64) as usize;
+            self.scan_range()
+                .rev()
+                .filter_map(|id| self.swaps.get(id).map(|swap| (id, swap)))
+                .take(limit)
+                .collect()
+        }
+
+        // Looks at most MAX_SCAN ids past the low-water mark, so it can return None while a
+        // swap is still stored after a long run of removed ids; later removals catch up.
+        #[ink(message)]
+        pub fn oldest_swap(&self) -> Option<u64> {
+            (self.oldest_id..self.swap_count)
+                .take(MAX_SCAN as usize)
+                .find(|id| self.swaps.contains(id))
+        }
+
+        // Walks ids [start_id, start_id + limit), skipping removed ones; limit is capped
+        // at MAX_LIST_LIMIT. Page forward until start_id reaches next_swap_id.
+        #[ink(message)]
+        pub fn list_swaps(&self, start_id: u64, limit: u64) -> Vec<(u64, Swap)> {
+            let end = start_id
+                .saturating_add(limit.min(MAX_LIST_LIMIT))
+                .min(self.swap_count);
+            (start_id..end)
+                .filter_map(|id| self.swaps.get(id).map(|swap| (id, swap)))
+                .collect()
+        }
+
+        // Unfilled token A on offer across live swaps offering `token`.
+        #[ink(message)]
+        pub fn open_liquidity(&self, token: AccountId) -> Balance {
+            self.scan_range()
+                .filter_map(|id| self.swaps.get(id).map(|swap| (id, swap)))
+                .filter(|(id, swap)| swap.token_a == token && self.is_live(*id, swap))
+                .fold(0, |total: Balance, (id, swap)| {
+                    total.saturating_add(self.refundable_a(id, &swap))
+                })
+        }
+
+        #[ink(message)]
+        pub fn code_hash(&self) -> Result<Hash> {
+            self.env().own_code_hash().map_err(|_| Error::CallFailed)
+        }
+
+        // Walks every issued id, so only meant for off-chain tests and fuzzing.
+        #[cfg(feature = "std")]
+        pub fn snapshot(&self) -> StateSnapshot {
+            StateSnapshot {
+                swaps: (0..self.swap_count)
+                    .filter_map(|id| self.swaps.get(id).map(|swap| (id, swap)))
+                    .collect(),
+                swap_count: self.swap_count,
+                active_swaps: self.active_swaps,
+                config: self.config(),
+            }
+        }
+
+        fn remove_swap(&mut self, swap_id: u64) {
+            if let Some(swap_data) = self.swaps.get(swap_id) {
+                self.active_swaps = self.active_swaps.saturating_sub(1);
+                let open = self.open_by_creator.get(swap_data.creator).unwrap_or(0);
+                self.open_by_creator
+                    .insert(swap_data.creator, &open.saturating_sub(1));
+                let open_for_token = self.open_by_token.get(swap_data.token_a).unwrap_or(0);
+                self.open_by_token
+                    .insert(swap_data.token_a, &open_for_token.saturating_sub(1));
+                let open_for_token_b = self.open_by_token_b.get(swap_data.token_b).unwrap_or(0);
+                self.open_by_token_b
+                    .insert(swap_data.token_b, &open_for_token_b.saturating_sub(1));
+            }
+            self.swaps.remove(swap_id);
+            self.target_ratios.remove(swap_id);
+            self.escrowed_a.remove(swap_id);
+            self.notes.remove(swap_id);
+            self.acceptor_callbacks.remove(swap_id);
+            self.min_fills.remove(swap_id);
+            self.oracle_bands.remove(swap_id);
+
+            let end = self.oldest_id.saturating_add(MAX_SCAN).min(self.swap_count);
+            while self.oldest_id < end && !self.swaps.contains(self.oldest_id) {
+                self.oldest_id += 1;
+            }
+        }
+
+        // A stored swap, or SwapCompleted for one that was filled and removed.
+        fn open_swap(&self, swap_id: u64) -> Result<Swap> {
+            self.swaps.get(swap_id).ok_or_else(|| {
+                if self.filled_totals.contains(swap_id) {
+                    Error::SwapCompleted
+                } else {
+                    Error::SwapNotFound
+                }
+            })
+        }
+
+        // Runs f with the reentrancy lock held, releasing it on every return path.
+        fn non_reentrant<T>(&mut self, f: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+            if self.locked.get().unwrap_or(false) {
+                return Err(Error::Reentrancy);
+            }
+            self.locked.set(&true);
+            let result = f(self);
+            self.locked.set(&false);
+            result
+        }
+
+        // Every stored swap is active, since completed ones are removed.
+        fn has_local_swaps(&self) -> bool {
+            self.active_swaps > 0
+        }
+
+        fn ensure_owner(&self) -> Result<()> {
+            if self.env().caller() != self.owner {
+                return Err(Error::Unauthorized);
+            }
+            Ok(())
+        }
+
+        // A frozen contract is reported as Frozen even when also paused.
+        fn ensure_not_paused(&self) -> Result<()> {
+            self.ensure_not_frozen()?;
+            if self.paused {
+                return Err(Error::Paused);
+            }
+            Ok(())
+        }
+
+        fn ensure_not_frozen(&self) -> Result<()> {
+            if self.frozen {
+                return Err(Error::Frozen);
+            }
+            Ok(())
+        }
+
+        // Most recent ids, bounded by MAX_SCAN.
+        fn scan_range(&self) -> core::ops::Range<u64> {
+            self.swap_count.saturating_sub(MAX_SCAN)..self.swap_count
+        }
+
+        // Not expired and still has escrowed token A left to pay out.
+        fn is_live(&self, swap_id: u64, swap: &Swap) -> bool {
+            self.env().block_number() <= swap.expiration
+                && swap.accepted_a < self.escrow_cap(swap_id, swap)
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use ink::env::test;
+
+        // Stands in for the token, oracle, acceptor, wrapper and delegate contracts, which
+        // the off-chain engine cannot call.
+        mod mock {
+            use super::*;
+            use std::cell::RefCell;
+            use std::collections::BTreeMap;
+
+            #[derive(Default)]
+            pub struct Chain {
+                // (token, holder) -> balance
+                pub balances: BTreeMap<(AccountId, AccountId), Balance>,
+                pub supplies: BTreeMap<AccountId, Balance>,
+                pub symbols: BTreeMap<AccountId, Vec<u8>>,
+                // flat amount withheld from every transfer of the token
+                pub transfer_fees: BTreeMap<AccountId, Balance>,
+                pub failing_tokens: Vec<AccountId>,
+                pub price: Option<Balance>,
+                pub rejecting_acceptors: Vec<AccountId>,
+                // (acceptor, swap id, amount_a, amount_b) per on_swap_accepted call
+                pub callbacks: Vec<(AccountId, u64, Balance, Balance)>,
+                // (creator, allowed_acceptor) per create_swap_delegate call
+                pub delegated: Vec<(AccountId, Option<AccountId>)>,
+                pub delegate_id: u64,
+                // (selector, gas limit) of every call, in order
+                pub calls: Vec<([u8; 4], u64)>,
+                // run once after the next successful transfer, e.g. to re-enter the contract
+                pub on_transfer: Option<fn()>,
+            }
+
+            thread_local! {
+                static CHAIN: RefCell<Chain> = RefCell::new(Chain::default());
+            }
+
+            pub fn reset() {
+                with(|chain| *chain = Chain::default());
+            }
+
+            pub fn with<R>(f: impl FnOnce(&mut Chain) -> R) -> R {
+                CHAIN.with(|chain| f(&mut chain.borrow_mut()))
+            }
+
+            pub fn balance(token: AccountId, holder: AccountId) -> Balance {
+                with(|chain| chain.balances.get(&(token, holder)).copied().unwrap_or(0))
+            }
+
+            pub fn mint(token: AccountId, holder: AccountId, amount: Balance) {
+                with(|chain| *chain.balances.entry((token, holder)).or_insert(0) += amount);
+            }
+
+            pub fn transfer(
+                token: AccountId,
+                from: AccountId,
+                to: AccountId,
+                amount: Balance,
+            ) -> Result<()> {
+                with(|chain| {
+                    let held = chain.balances.get(&(token, from)).copied().unwrap_or(0);
+                    if chain.failing_tokens.contains(&token) || held < amount {
+                        return Err(Error::TransferFailed);
+                    }
+                    let fee = chain.transfer_fees.get(&token).copied().unwrap_or(0);
+                    chain.balances.insert((token, from), held - amount);
+                    *chain.balances.entry((token, to)).or_insert(0) += amount.saturating_sub(fee);
+                    Ok(())
+                })?;
+
+                if let Some(hook) = with(|chain| chain.on_transfer.take()) {
+                    hook();
+                }
+                Ok(())
+            }
+        }
+
+        impl TokenSwap {
+            pub(super) fn get_balance(
+                &self,
+                token_contract: AccountId,
+                account: AccountId,
+                gas_limit: u64,
+            ) -> Result<Balance> {
+                mock::with(|chain| chain.calls.push((BALANCE_OF_SELECTOR, gas_limit)));
+                Ok(mock::balance(token_contract, account))
+            }
+
+            pub(super) fn get_total_supply(
+                &self,
+                token_contract: AccountId,
+                gas_limit: u64,
+            ) -> Result<Balance> {
+                mock::with(|chain| {
+                    chain.calls.push((TOTAL_SUPPLY_SELECTOR, gas_limit));
+                    chain.supplies.get(&token_contract).copied()
+                })
+                .ok_or(Error::CallFailed)
+            }
+
+            pub(super) fn get_oracle_price(
+                &self,
+                _oracle: AccountId,
+                _token_a: AccountId,
+                _token_b: AccountId,
+                gas_limit: u64,
+            ) -> Result<Balance> {
+                mock::with(|chain| {
+                    chain.calls.push((PRICE_SELECTOR, gas_limit));
+                    chain.price
+                })
+                .ok_or(Error::CallFailed)
+            }
+
+            pub(super) fn fetch_symbol(
+                &self,
+                token_contract: AccountId,
+                gas_limit: u64,
+            ) -> Option<Vec<u8>> {
+                mock::with(|chain| {
+                    chain.calls.push((TOKEN_SYMBOL_SELECTOR, gas_limit));
+                    chain.symbols.get(&token_contract).cloned()
+                })
+            }
+
+            #[allow(clippy::too_many_arguments)]
+            pub(super) fn delegate_create_swap(
+                &self,
+                _delegate: AccountId,
+                creator: AccountId,
+                _token_a: AccountId,
+                _token_b: AccountId,
+                _amount_a: Balance,
+                _amount_b: Balance,
+                _duration: BlockNumber,
+                allowed_acceptor: Option<AccountId>,
+                gas_limit: u64,
+            ) -> Result<u64> {
+                Ok(mock::with(|chain| {
+                    chain
+                        .calls
+                        .push((ink::selector_bytes!("create_swap_delegate"), gas_limit));
+                    chain.delegated.push((creator, allowed_acceptor));
+                    chain.delegate_id
+                }))
+            }
+
+            pub(super) fn notify_acceptor(
+                &self,
+                acceptor: AccountId,
+                swap_id: u64,
+                amount_a: Balance,
+                amount_b: Balance,
+                gas_limit: u64,
+            ) -> Result<()> {
+                mock::with(|chain| {
+                    chain.calls.push((ON_SWAP_ACCEPTED_SELECTOR, gas_limit));
+                    if chain.rejecting_acceptors.contains(&acceptor) {
+                        return Err(Error::CallbackFailed);
+                    }
+                    chain
+                        .callbacks
+                        .push((acceptor, swap_id, amount_a, amount_b));
+                    Ok(())
+                })
+            }
+
+            pub(super) fn transfer_token(
+                &self,
+                token_contract: AccountId,
+                from: AccountId,
+                to: AccountId,
+                amount: Balance,
+                gas_limit: u64,
+            ) -> Result<()> {
+                mock::with(|chain| chain.calls.push((TRANSFER_SELECTOR, gas_limit)));
+                mock::transfer(token_contract, from, to, amount)
+            }
+
+            pub(super) fn deposit_native(
+                &self,
+                wrapper: AccountId,
+                amount: Balance,
+                gas_limit: u64,
+            ) -> Result<()> {
+                mock::with(|chain| chain.calls.push((DEPOSIT_SELECTOR, gas_limit)));
+                mock::mint(wrapper, contract_id(), amount);
+                Ok(())
+            }
+        }
+
+        fn token_a() -> AccountId {
+            AccountId::from([0xA0; 32])
+        }
+
+        fn token_b() -> AccountId {
+            AccountId::from([0xB0; 32])
+        }
+
+        fn contract_id() -> AccountId {
+            AccountId::from([0xC0; 32])
+        }
+
+        fn accounts() -> test::DefaultAccounts<DefaultEnvironment> {
+            test::default_accounts::<DefaultEnvironment>()
+        }
+
+        fn set_caller(caller: AccountId) {
+            test::set_caller::<DefaultEnvironment>(caller);
+        }
+
+        // Owned by alice; alice, bob and charlie each hold 1_000 of both tokens.
+        fn setup() -> TokenSwap {
+            mock::reset();
+            test::set_callee::<DefaultEnvironment>(contract_id());
+            let accounts = accounts();
+            for holder in [accounts.alice, accounts.bob, accounts.charlie] {
+                mock::mint(token_a(), holder, 1_000);
+                mock::mint(token_b(), holder, 1_000);
+            }
+            set_caller(accounts.alice);
+            TokenSwap::new()
+        }
+
+        // alice offers amount_a of token A for amount_b of token B to anyone for 100 blocks.
+        fn create(contract: &mut TokenSwap, amount_a: Balance, amount_b: Balance) -> u64 {
+            set_caller(accounts().alice);
+            contract
+                .create_swap(token_a(), token_b(), amount_a, amount_b, 100, None, false)
+                .expect("create_swap failed")
+        }
+
+        type Event = <TokenSwap as ink::reflect::ContractEventBase>::Type;
+
+        fn events() -> Vec<Event> {
+            test::recorded_events()
+                .map(|event| <Event as scale::Decode>::decode(&mut &event.data[..]).unwrap())
+                .collect()
+        }
+
+        // Creates and cancels `count` swaps, pushing older ids out of the MAX_SCAN window.
+        fn bury(contract: &mut TokenSwap, count: u64) {
+            for _ in 0..count {
+                let id = create(contract, 1, 1);
+                contract
+                    .cancel_and_refund(id)
+                    .expect("cancel_and_refund failed");
+            }
+        }
+
+        #[ink::test]
+        fn get_swap_returns_the_stored_swap() {
+            let mut contract = setup();
+            let id = create(&mut contract, 100, 200);
+
+            let swap = contract.get_swap(id).unwrap();
+            assert_eq!(swap.creator, accounts().alice);
+            assert_eq!((swap.token_a, swap.token_b), (token_a(), token_b()));
+            assert_eq!((swap.required_a, swap.required_b), (100, 200));
+            assert_eq!((swap.accepted_a, swap.accepted_b), (0, 0));
+            assert_eq!(swap.expiration, 100);
+            assert_eq!(swap.allowed_acceptor, None);
+        }
+
+        #[ink::test]
+        fn get_swap_rejects_unknown_ids() {
+            let mut contract = setup();
+            create(&mut contract, 100, 200);
+
+            assert_eq!(contract.get_swap(1), Err(Error::SwapNotFound));
+        }
+
+        #[ink::test]
+        fn accept_pays_token_a_for_token_b_at_the_listed_price() {
+            let mut contract = setup();
+            let id = create(&mut contract, 100, 200);
+            let (alice, bob) = (accounts().alice, accounts().bob);
+
+            set_caller(bob);
+            assert_eq!(contract.accept_swap(id, 50, 100), Ok(()));
+
+            assert_eq!(mock::balance(token_a(), bob), 1_050);
+            assert_eq!(mock::balance(token_b(), bob), 900);
+            assert_eq!(mock::balance(token_b(), alice), 1_100);
+            assert_eq!(mock::balance(token_a(), contract_id()), 50);
+        }
+
+        #[ink::test]
+        fn accept_rejects_fills_below_the_listed_price() {
+            let mut contract = setup();
+            let id = create(&mut contract, 100, 200);
+
+            set_caller(accounts().bob);
+            assert_eq!(contract.accept_swap(id, 100, 0), Err(Error::UnderPriced));
+            assert_eq!(contract.accept_swap(id, 50, 99), Err(Error::UnderPriced));
+
+            assert_eq!(mock::balance(token_a(), contract_id()), 100);
+            assert_eq!(mock::balance(token_a(), accounts().bob), 1_000);
+        }
+
+        #[ink::test]
+        fn native_accept_rejects_fills_below_the_listed_price() {
+            let mut contract = setup();
+            contract.set_native_wrapper(Some(token_b())).unwrap();
+            let id = create(&mut contract, 100, 200);
+
+            set_caller(accounts().bob);
+            assert_eq!(
+                contract.accept_swap_with_native(id, 100, 0),
+                Err(Error::UnderPriced)
+            );
+            assert_eq!(mock::balance(token_a(), contract_id()), 100);
+        }
+
+        #[ink::test]
+        fn safe_create_records_the_escrow_a_fee_token_delivered() {
+            let mut contract = setup();
+            mock::with(|chain| chain.transfer_fees.insert(token_a(), 5));
+
+            let (id, escrowed) = contract
+                .safe_create_swap(token_a(), token_b(), 100, 200, 100, None, false)
+                .unwrap();
+
+            assert_eq!(escrowed, 95);
+            assert_eq!(contract.get_swap(id).unwrap().required_a, 95);
+            assert_eq!(contract.target_ratio(id), Ok((95, 200)));
+        }
+
+        #[ink::test]
+        fn refund_of_a_short_escrow_leaves_other_swaps_escrow_alone() {
+            let mut contract = setup();
+            let (alice, bob, charlie) = (accounts().alice, accounts().bob, accounts().charlie);
+            set_caller(charlie);
+            contract
+                .create_swap(token_a(), token_b(), 100, 200, 100, None, false)
+                .unwrap();
+            mock::with(|chain| chain.transfer_fees.insert(token_a(), 5));
+            let id = create(&mut contract, 100, 200);
+            assert_eq!(mock::balance(token_a(), contract_id()), 195);
+
+            set_caller(bob);
+            assert_eq!(contract.accept_swap(id, 50, 100), Ok(()));
+            assert_eq!(
+                contract.accept_swap(id, 50, 100),
+                Err(Error::InsufficientBalance)
+            );
+
+            set_caller(alice);
+            assert_eq!(contract.cancel_and_refund(id), Ok(()));
+
+            // 95 arrived, 50 went to bob and 45 back to alice; charlie's 100 is untouched.
+            assert_eq!(mock::balance(token_a(), contract_id()), 100);
+            assert_eq!(mock::balance(token_a(), alice), 900 + 40);
+        }
+
+        #[ink::test]
+        fn cross_contract_calls_forward_the_configured_gas_limit() {
+            let mut contract = setup();
+            let gas_of_calls = || {
+                mock::with(|chain| {
+                    chain
+                        .calls
+                        .drain(..)
+                        .map(|(_, gas)| gas)
+                        .collect::<Vec<_>>()
+                })
+            };
+
+            assert_eq!(contract.set_call_gas_limit(7_000), Ok(()));
+            assert_eq!(contract.effective_xcall_gas(), 7_000);
+            mock::with(|chain| chain.calls.clear());
+            create(&mut contract, 100, 200);
+            let gas = gas_of_calls();
+            assert!(!gas.is_empty());
+            assert!(gas.iter().all(|&g| g == 7_000));
+
+            // 0 forwards all remaining gas, as with ink's own gas_limit(0).
+            assert_eq!(contract.set_call_gas_limit(0), Ok(()));
+            assert_eq!(contract.effective_xcall_gas(), 0);
+            let id = create(&mut contract, 100, 200);
+            set_caller(accounts().bob);
+            assert_eq!(contract.accept_swap(id, 50, 100), Ok(()));
+            let gas = gas_of_calls();
+            assert!(gas.len() > 3);
+            assert!(gas.iter().all(|&g| g == 0));
+
+            assert_eq!(contract.set_call_gas_limit(1), Err(Error::Unauthorized));
+        }
+
+        #[ink::test]
+        fn has_escrow_sees_swaps_older_than_the_scan_window() {
+            let mut contract = setup();
+            assert!(!contract.has_escrow());
+            let id = create(&mut contract, 100, 200);
+            bury(&mut contract, MAX_SCAN);
+            assert!(contract.has_escrow());
+
+            assert_eq!(contract.cancel_and_refund(id), Ok(()));
+            assert!(!contract.has_escrow());
+        }
+
+        #[ink::test]
+        fn token_in_use_counts_both_legs_of_old_swaps() {
+            let mut contract = setup();
+            let other = AccountId::from([0xD0; 32]);
+            mock::mint(other, accounts().alice, 1_000);
+            set_caller(accounts().alice);
+            let id = contract
+                .create_swap(other, token_b(), 100, 200, 100, None, false)
+                .unwrap();
+            bury(&mut contract, MAX_SCAN);
+            assert!(contract.token_in_use(other));
+            assert!(contract.token_in_use(token_b()));
+
+            let id_b = contract
+                .create_swap(token_a(), other, 100, 200, 1, None, false)
+                .unwrap();
+            assert_eq!(contract.cancel_and_refund(id), Ok(()));
+            assert!(contract.token_in_use(other));
+
+            assert_eq!(contract.cancel_and_refund(id_b), Ok(()));
+            assert!(!contract.token_in_use(other));
+        }
+
+        #[ink::test]
+        fn delegation_waits_until_no_local_swap_is_left() {
+            let mut contract = setup();
+            let delegate = AccountId::from([0xDE; 32]);
+            let id = create(&mut contract, 100, 200);
+            bury(&mut contract, 3);
+            assert_eq!(
+                contract.set_delegated_contract(delegate),
+                Err(Error::LocalSwapsExist)
+            );
+
+            assert_eq!(contract.cancel_and_refund(id), Ok(()));
+            assert_eq!(contract.set_delegated_contract(delegate), Ok(()));
+        }
+
+        #[ink::test]
+        fn oldest_swap_skips_removed_swaps() {
+            let mut contract = setup();
+            assert_eq!(contract.oldest_swap(), None);
+            let ids: Vec<u64> = (0..4).map(|_| create(&mut contract, 10, 20)).collect();
+            assert_eq!(contract.oldest_swap(), Some(ids[0]));
+
+            assert_eq!(contract.cancel_and_refund(ids[1]), Ok(()));
+            assert_eq!(contract.oldest_swap(), Some(ids[0]));
+            assert_eq!(contract.cancel_and_refund(ids[0]), Ok(()));
+            assert_eq!(contract.oldest_swap(), Some(ids[2]));
+
+            set_caller(accounts().bob);
+            assert_eq!(contract.accept_swap(ids[2], 10, 20), Ok(()));
+            assert_eq!(contract.oldest_swap(), Some(ids[3]));
+        }
+
+        #[ink::test]
+        fn completed_swap_is_removed_but_keeps_its_totals() {
+            let mut contract = setup();
+            let id = create(&mut contract, 100, 200);
+            set_caller(accounts().bob);
+            assert_eq!(contract.accept_swap(id, 40, 80), Ok(()));
+            assert_eq!(contract.accept_swap(id, 60, 120), Ok(()));
+
+            let accepted = events()
+                .into_iter()
+                .filter_map(|event| match event {
+                    Event::SwapAccepted(accepted) => Some(accepted.completed),
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(accepted, [false, true]);
+            let fully_filled = events()
+                .into_iter()
+                .filter(|event| matches!(event, Event::SwapFullyFilled(filled) if filled.id == id))
+                .count();
+            assert_eq!(fully_filled, 1);
+
+            assert_eq!(contract.get_swap(id), Err(Error::SwapNotFound));
+            assert_eq!(contract.active_swap_count(), 0);
+            assert_eq!(contract.accept_swap(id, 1, 2), Err(Error::SwapCompleted));
+            assert_eq!(contract.proceeds(id), Ok(200));
+            assert_eq!(contract.average_price(id), Ok((100, 200)));
+            assert_eq!(contract.target_ratio(id), Ok((100, 200)));
+            assert_eq!(contract.proceeds(id + 1), Err(Error::SwapNotFound));
+        }
+
+        #[ink::test]
+        fn precheck_reports_what_accept_would_fail_with() {
+            let mut contract = setup();
+            let bob = accounts().bob;
+            let id = create(&mut contract, 100, 200);
+            let reserved = contract
+                .create_swap(
+                    token_a(),
+                    token_b(),
+                    10,
+                    20,
+                    100,
+                    Some(accounts().charlie),
+                    false,
+                )
+                .unwrap();
+            assert_eq!(contract.accept_precheck(id, bob, 10, 20), Ok(()));
+            assert_eq!(
+                contract.accept_precheck(id, bob, 10, 0),
+                Err(Error::UnderPriced)
+            );
+
+            // Runs both as bob, checks they agree and returns the shared result; none of these
+            // fills moves any token.
+            let both = |contract: &mut TokenSwap, id, amount_a, amount_b| {
+                set_caller(bob);
+                let precheck = contract.accept_precheck(id, bob, amount_a, amount_b);
+                assert_eq!(precheck, contract.accept_swap(id, amount_a, amount_b));
+                precheck
+            };
+            assert_eq!(both(&mut contract, 99, 10, 20), Err(Error::SwapNotFound));
+            assert_eq!(
+                both(&mut contract, reserved, 10, 20),
+                Err(Error::Unauthorized)
+            );
+            assert_eq!(
+                both(&mut contract, id, 200, 400),
+                Err(Error::InsufficientBalance)
+            );
+            for _ in 0..101 {
+                test::advance_block::<DefaultEnvironment>();
+            }
+            assert_eq!(both(&mut contract, id, 10, 20), Err(Error::SwapExpired));
+
+            set_caller(accounts().alice);
+            assert_eq!(contract.pause(), Ok(()));
+            assert_eq!(
+                contract.accept_precheck(id, bob, 10, 20),
+                Err(Error::Paused)
+            );
+            set_caller(bob);
+            assert_eq!(contract.accept_swap(id, 10, 20), Err(Error::Paused));
+        }
+
+        #[ink::test]
+        fn accept_rejects_an_empty_fill() {
+            let mut contract = setup();
+            let id = create(&mut contract, 100, 200);
+            set_caller(accounts().bob);
+            assert_eq!(contract.accept_swap(id, 0, 0), Err(Error::ZeroAmount));
+            assert_eq!(contract.total_fills, 0);
+        }
+
+        #[ink::test]
+        fn counter_and_fee_overflows_report_overflow() {
+            let mut contract = setup();
+            let create_with = |contract: &mut TokenSwap, duration| {
+                contract.create_swap(token_a(), token_b(), 10, 20, duration, None, false)
+            };
+
+            contract.max_duration = BlockNumber::MAX;
+            test::advance_block::<DefaultEnvironment>();
+            assert_eq!(
+                create_with(&mut contract, BlockNumber::MAX),
+                Err(Error::Overflow)
+            );
+
+            contract.swap_count = u64::MAX;
+            assert_eq!(create_with(&mut contract, 100), Err(Error::Overflow));
+            contract.swap_count = 0;
+
+            assert_eq!(contract.set_creation_fee(1), Ok(()));
+            contract.collected_fees = Balance::MAX;
+            test::set_value_transferred::<DefaultEnvironment>(1);
+            assert_eq!(create_with(&mut contract, 100), Err(Error::Overflow));
+
+            contract.collected_fees = 0;
+            let id = create_with(&mut contract, 100).unwrap();
+            set_caller(accounts().bob);
+            assert_eq!(contract.accept_swap(id, 1, 2), Ok(()));
+            assert_eq!(
+                contract.accept_swap(id, Balance::MAX, 2),
+                Err(Error::Overflow)
+            );
+        }
+
+        // alice's 100 A for 200 B swap, opted into oracle settlement within band_bps, with
+        // the oracle quoting `price` B per PRICE_SCALE of A.
+        fn oracle_settled_swap(band_bps: Option<u32>, price: Balance) -> (TokenSwap, u64) {
+            let mut contract = setup();
+            let id = create(&mut contract, 100, 200);
+            assert_eq!(contract.set_oracle_settlement(id, band_bps), Ok(()));
+            assert_eq!(
+                contract.set_oracle(Some(AccountId::from([0x0E; 32]))),
+                Ok(())
+            );
+            mock::with(|chain| chain.price = Some(price));
+            set_caller(accounts().bob);
+            (contract, id)
+        }
+
+        #[ink::test]
+        fn oracle_fill_settles_at_an_in_band_rate() {
+            // 1% below the listed 2 B per A, inside the creator's 2% band.
+            let (mut contract, id) = oracle_settled_swap(Some(200), 198 * PRICE_SCALE / 100);
+            assert_eq!(contract.accept_swap_at_oracle_rate(id, 99), Ok(50));
+            assert_eq!(mock::balance(token_a(), accounts().bob), 1_050);
+            assert_eq!(mock::balance(token_b(), accounts().alice), 1_099);
+
+            // The same amounts at the listed ratio would be under-priced.
+            assert_eq!(contract.accept_swap(id, 50, 99), Err(Error::UnderPriced));
+
+            assert_eq!(contract.accept_swap_at_oracle_rate(id, 99), Ok(50));
+            assert_eq!(contract.get_swap(id), Err(Error::SwapNotFound));
+            assert_eq!(contract.proceeds(id), Ok(198));
+        }
+
+        #[ink::test]
+        fn oracle_fill_rejects_an_out_of_band_rate() {
+            // 5% above the listed price, outside the creator's 2% band.
+            let (mut contract, id) = oracle_settled_swap(Some(200), 210 * PRICE_SCALE / 100);
+            assert_eq!(
+                contract.accept_swap_at_oracle_rate(id, 105),
+                Err(Error::PriceOutOfBand)
+            );
+            assert_eq!(mock::balance(token_b(), accounts().bob), 1_000);
+        }
+
+        #[ink::test]
+        fn oracle_fill_needs_the_creator_to_opt_in() {
+            let (mut contract, id) = oracle_settled_swap(None, 2 * PRICE_SCALE);
+            assert_eq!(
+                contract.accept_swap_at_oracle_rate(id, 20),
+                Err(Error::OracleSettlementDisabled)
+            );
+            assert_eq!(
+                contract.set_oracle_settlement(id, Some(100)),
+                Err(Error::Unauthorized)
+            );
+        }
+
+        #[ink::test]
+        fn set_config_sets_and_clears_optional_accounts() {
+            let mut contract = setup();
+            let account = AccountId::from([0x0E; 32]);
+            let set = ConfigUpdate {
+                delegated_contract: Some(Some(account)),
+                oracle: Some(Some(account)),
+                native_wrapper: Some(Some(account)),
+                ..Default::default()
+            };
+            assert_eq!(contract.set_config(set), Ok(()));
+            let config = contract.config();
+            assert_eq!(config.delegated_contract, Some(account));
+            assert_eq!(config.oracle, Some(account));
+            assert_eq!(config.native_wrapper, Some(account));
+
+            assert_eq!(contract.set_config(ConfigUpdate::default()), Ok(()));
+            assert_eq!(contract.config().oracle, Some(account));
+
+            let clear = ConfigUpdate {
+                delegated_contract: Some(None),
+                oracle: Some(None),
+                native_wrapper: Some(None),
+                ..Default::default()
+            };
+            assert_eq!(contract.set_config(clear), Ok(()));
+            let config = contract.config();
+            assert_eq!(config.delegated_contract, None);
+            assert_eq!(config.oracle, None);
+            assert_eq!(config.native_wrapper, None);
+        }
+
+        #[ink::test]
+        fn latest_swaps_caps_limits_above_u32() {
+            let mut contract = setup();
+            let ids: Vec<u64> = (0..5).map(|_| create(&mut contract, 10, 20)).collect();
+            assert_eq!(contract.delete_swap(ids[2]), Ok(()));
+            let latest_ids = |contract: &TokenSwap, limit: u64| -> Vec<u64> {
+                let latest = contract.latest_swaps(limit);
+                latest.into_iter().map(|(id, _)| id).collect()
+            };
+
+            // Newest first, the removed id skipped, stopping at the limit.
+            assert_eq!(latest_ids(&contract, 3), [ids[4], ids[3], ids[1]]);
+            // On wasm32, (1 << 32) + 1 truncates to 1 unless it is capped while still a u64.
+            assert_eq!(
+                latest_ids(&contract, (1 << 32) + 1),
+                [ids[4], ids[3], ids[1], ids[0]]
+            );
+
+            for _ in 0..MAX_SWAPS_PER_QUERY {
+                create(&mut contract, 10, 20);
+            }
+            assert_eq!(contract.latest_swaps(u64::MAX).len(), MAX_SWAPS_PER_QUERY);
+        }
+
+        #[ink::test]
+        fn batch_with_an_invalid_third_entry_changes_nothing() {
+            let mut contract = setup();
+            let ids: Vec<u64> = (0..3).map(|_| create(&mut contract, 100, 200)).collect();
+            let before = contract.snapshot();
+            let bob = accounts().bob;
+
+            set_caller(bob);
+            assert_eq!(
+                contract.accept_swaps(vec![(ids[0], 10, 20), (ids[1], 100, 200), (ids[2], 10, 0)]),
+                Err(Error::UnderPriced)
+            );
+
+            assert_eq!(contract.snapshot(), before);
+            assert_eq!(contract.total_fills, 0);
+            assert_eq!(mock::balance(token_a(), bob), 1_000);
+            assert_eq!(mock::balance(token_b(), bob), 1_000);
+
+            assert_eq!(
+                contract.accept_swaps(vec![(ids[0], 10, 20), (ids[1], 100, 200)]),
+                Ok(())
+            );
+            assert_eq!(contract.total_fills, 2);
+            assert_eq!(mock::balance(token_a(), bob), 1_110);
+        }
+
+        #[ink::test]
+        fn cancel_refunds_the_unfilled_escrow_after_a_partial_fill() {
+            let mut contract = setup();
+            let alice = accounts().alice;
+            let id = create(&mut contract, 100, 200);
+            set_caller(accounts().bob);
+            assert_eq!(contract.accept_swap(id, 30, 60), Ok(()));
+            assert_eq!(contract.cancel_and_refund(id), Err(Error::Unauthorized));
+
+            set_caller(alice);
+            assert_eq!(contract.cancel_and_refund(id), Ok(()));
+            assert_eq!(mock::balance(token_a(), alice), 970);
+            assert_eq!(mock::balance(token_b(), alice), 1_060);
+            assert_eq!(mock::balance(token_a(), contract_id()), 0);
+            assert_eq!(contract.get_swap(id), Err(Error::SwapNotFound));
+            assert_eq!(contract.cancel_and_refund(id), Err(Error::SwapNotFound));
+        }
+
+        #[ink::test]
+        fn cancel_without_fills_refunds_everything() {
+            let mut contract = setup();
+            let id = create(&mut contract, 100, 200);
+            assert_eq!(contract.cancel_and_refund(id), Ok(()));
+            assert_eq!(mock::balance(token_a(), accounts().alice), 1_000);
+            assert!(
+                matches!(events().last(), Some(Event::SwapDeleted(deleted)) if deleted.id == id)
+            );
+        }
+
+        #[ink::test]
+        fn ownership_moves_once_the_new_owner_accepts() {
+            let mut contract = setup();
+            let (alice, bob) = (accounts().alice, accounts().bob);
+            assert_eq!(contract.owner(), alice);
+            assert_eq!(contract.transfer_ownership(bob), Ok(()));
+            assert_eq!(contract.owner(), alice);
+
+            set_caller(bob);
+            assert_eq!(contract.accept_ownership(), Ok(()));
+            assert_eq!(contract.owner(), bob);
+            assert!(matches!(
+                events().last(),
+                Some(Event::OwnershipTransferred(transferred))
+                    if transferred.previous_owner == alice && transferred.new_owner == bob
+            ));
+            assert_eq!(contract.accept_ownership(), Err(Error::Unauthorized));
+        }
+
+        #[ink::test]
+        fn only_the_owner_starts_an_ownership_transfer() {
+            let mut contract = setup();
+            set_caller(accounts().bob);
+            assert_eq!(
+                contract.transfer_ownership(accounts().bob),
+                Err(Error::Unauthorized)
+            );
+            assert_eq!(contract.accept_ownership(), Err(Error::Unauthorized));
+            assert_eq!(contract.owner(), accounts().alice);
+        }
+
+        #[ink::test]
+        fn only_the_pending_owner_accepts_ownership() {
+            let mut contract = setup();
+            assert_eq!(contract.transfer_ownership(accounts().bob), Ok(()));
+            set_caller(accounts().charlie);
+            assert_eq!(contract.accept_ownership(), Err(Error::Unauthorized));
+            assert_eq!(contract.owner(), accounts().alice);
+        }
+
+        #[ink::test]
+        fn pause_blocks_create_and_accept_but_not_delete() {
+            let mut contract = setup();
+            let id = create(&mut contract, 100, 200);
+            set_caller(accounts().bob);
+            assert_eq!(contract.pause(), Err(Error::Unauthorized));
+
+            set_caller(accounts().alice);
+            assert_eq!(contract.pause(), Ok(()));
+            assert_eq!(
+                contract.create_swap(token_a(), token_b(), 10, 20, 100, None, false),
+                Err(Error::Paused)
+            );
+            set_caller(accounts().bob);
+            assert_eq!(contract.accept_swap(id, 10, 20), Err(Error::Paused));
+            assert_eq!(
+                contract.accept_swaps(vec![(id, 10, 20)]),
+                Err(Error::Paused)
+            );
+
+            set_caller(accounts().alice);
+            assert_eq!(contract.delete_swap(id), Ok(()));
+            assert_eq!(mock::balance(token_a(), accounts().alice), 1_000);
+
+            assert_eq!(contract.unpause(), Ok(()));
+            create(&mut contract, 100, 200);
+        }
+
+        #[ink::test]
+        fn fills_below_the_minimum_are_rejected() {
+            let mut contract = setup();
+            let id = create(&mut contract, 100, 200);
+            assert_eq!(contract.set_min_fill(id, 20, 40), Ok(()));
+
+            set_caller(accounts().bob);
+            assert_eq!(contract.set_min_fill(id, 0, 0), Err(Error::Unauthorized));
+            assert_eq!(contract.accept_swap(id, 10, 20), Err(Error::FillTooSmall));
+            assert_eq!(contract.accept_swap(id, 20, 40), Ok(()));
+        }
+
+        #[ink::test]
+        fn a_below_minimum_fill_that_closes_the_swap_is_allowed() {
+            let mut contract = setup();
+            let id = create(&mut contract, 100, 200);
+            assert_eq!(contract.set_min_fill(id, 20, 40), Ok(()));
+
+            set_caller(accounts().bob);
+            assert_eq!(contract.accept_swap(id, 90, 180), Ok(()));
+            assert_eq!(contract.accept_swap(id, 10, 20), Ok(()));
+            assert!(!contract.swap_exists(id));
+        }
+
+        // Invariants any sequence of messages must preserve, checked against snapshot().
+        fn assert_invariants(contract: &TokenSwap) {
+            let state = contract.snapshot();
+            assert_eq!(state.active_swaps, state.swaps.len() as u64);
+            let mut escrow: Balance = 0;
+            for (id, swap) in &state.swaps {
+                assert!(*id < state.swap_count);
+                assert!(swap.accepted_a < swap.required_a);
+                assert!(swap.accepted_b <= swap.required_b);
+                // Fills never pay less than the listed price.
+                assert!(swap.accepted_b * swap.required_a >= swap.accepted_a * swap.required_b);
+                escrow += swap.required_a - swap.accepted_a;
+            }
+            assert!(mock::balance(token_a(), contract_id()) >= escrow);
+        }
+
+        #[ink::test]
+        fn snapshot_invariants_hold_across_a_message_sequence() {
+            let mut contract = setup();
+            assert_invariants(&contract);
+            let (alice, bob, charlie) = (accounts().alice, accounts().bob, accounts().charlie);
+
+            let mut ids = Vec::new();
+            for (creator, amount_a, amount_b) in
+                [(alice, 100, 200), (charlie, 30, 90), (alice, 7, 3)]
+            {
+                set_caller(creator);
+                ids.push(
+                    contract
+                        .create_swap(token_a(), token_b(), amount_a, amount_b, 10, None, false)
+                        .unwrap(),
+                );
+                assert_invariants(&contract);
+            }
+
+            set_caller(bob);
+            let fills = [
+                (ids[0], 40, 80),
+                (ids[1], 10, 30),
+                (ids[0], 60, 120),
+                (ids[2], 7, 3),
+            ];
+            for (id, amount_a, amount_b) in fills {
+                assert_eq!(contract.accept_swap(id, amount_a, amount_b), Ok(()));
+                assert_invariants(&contract);
+            }
+            assert_eq!(contract.accept_swap(ids[1], 1, 1), Err(Error::UnderPriced));
+            assert_invariants(&contract);
+
+            for _ in 0..11 {
+                test::advance_block::<DefaultEnvironment>();
+            }
+            assert_eq!(contract.reap_expired(ids[1]), Ok(()));
+            assert_invariants(&contract);
+            assert!(contract.snapshot().swaps.is_empty());
+        }
+
+        #[ink::test]
+        fn create_rejects_underpaid_creation_fee() {
+            let mut contract = setup();
+            contract.set_creation_fee(10).unwrap();
+
+            test::set_value_transferred::<DefaultEnvironment>(9);
+            assert_eq!(
+                contract.create_swap(token_a(), token_b(), 100, 200, 100, None, false),
+                Err(Error::InsufficientBalance)
+            );
+            assert!(!contract.swap_exists(0));
+        }
+
+        #[ink::test]
+        fn create_accepts_exact_creation_fee_and_owner_withdraws_it() {
+            let mut contract = setup();
+            let charlie = accounts().charlie;
+            contract.set_creation_fee(10).unwrap();
+
+            test::set_value_transferred::<DefaultEnvironment>(10);
+            test::set_account_balance::<DefaultEnvironment>(contract_id(), 10);
+            assert_eq!(
+                contract.create_swap(token_a(), token_b(), 100, 200, 100, None, false),
+                Ok(0)
+            );
+            test::set_value_transferred::<DefaultEnvironment>(0);
+
+            let before = test::get_account_balance::<DefaultEnvironment>(charlie).unwrap();
+            assert_eq!(contract.withdraw_fees(charlie), Ok(()));
+            assert_eq!(
+                test::get_account_balance::<DefaultEnvironment>(charlie).unwrap(),
+                before + 10
+            );
+        }
+
+        #[ink::test]
+        fn only_the_owner_withdraws_fees() {
+            let mut contract = setup();
+            let bob = accounts().bob;
+
+            set_caller(bob);
+            assert_eq!(contract.withdraw_fees(bob), Err(Error::Unauthorized));
+            assert_eq!(contract.set_creation_fee(1), Err(Error::Unauthorized));
+        }
+
+        // A swap reserved for django, registered as a contract with the callback enabled.
+        fn create_for_contract_acceptor(contract: &mut TokenSwap) -> (u64, AccountId) {
+            let django = accounts().django;
+            test::set_contract::<DefaultEnvironment>(django);
+            mock::mint(token_b(), django, 1_000);
+
+            let id = create(contract, 100, 200);
+            contract.set_allowed_acceptor(id, Some(django)).unwrap();
+            contract.set_acceptor_callback(id, true).unwrap();
+            (id, django)
+        }
+
+        #[ink::test]
+        fn accept_notifies_a_contract_acceptor() {
+            let mut contract = setup();
+            let (id, django) = create_for_contract_acceptor(&mut contract);
+
+            set_caller(django);
+            assert_eq!(contract.accept_swap(id, 50, 100), Ok(()));
+
+            assert_eq!(
+                mock::with(|chain| chain.callbacks.clone()),
+                vec![(django, id, 50, 100)]
+            );
+            assert_eq!(mock::balance(token_a(), django), 50);
+        }
+
+        #[ink::test]
+        fn rejecting_callback_fails_the_accept_before_any_transfer() {
+            let mut contract = setup();
+            let (id, django) = create_for_contract_acceptor(&mut contract);
+            mock::with(|chain| chain.rejecting_acceptors.push(django));
+
+            set_caller(django);
+            assert_eq!(
+                contract.accept_swap(id, 50, 100),
+                Err(Error::CallbackFailed)
+            );
+
+            assert_eq!(mock::balance(token_a(), django), 0);
+            assert_eq!(mock::balance(token_b(), django), 1_000);
+            assert_eq!(mock::balance(token_a(), contract_id()), 100);
+        }
+
+        // Plays a malicious token calling back into accept_swap from inside a transfer.
+        fn reenter_accept_swap() {
+            let mut reentrant = TokenSwap::new();
+            assert_eq!(reentrant.accept_swap(0, 10, 20), Err(Error::Reentrancy));
+        }
+
+        #[ink::test]
+        fn accept_reentered_from_a_token_transfer_fails() {
+            let mut contract = setup();
+            let id = create(&mut contract, 100, 200);
+            mock::with(|chain| chain.on_transfer = Some(reenter_accept_swap));
+
+            set_caller(accounts().bob);
+            assert_eq!(contract.accept_swap(id, 50, 100), Ok(()));
+            assert!(mock::with(|chain| chain.on_transfer.is_none()));
+            assert_eq!(contract.get_swap(id).unwrap().accepted_a, 50);
+
+            // The lock is released once the outer accept returns.
+            assert_eq!(contract.accept_swap(id, 10, 20), Ok(()));
+        }
+
+        #[ink::test]
+        fn failed_token_a_payout_leaves_token_b_with_the_acceptor() {
+            let mut contract = setup();
+            let id = create(&mut contract, 100, 200);
+            mock::with(|chain| chain.failing_tokens.push(token_a()));
+
+            set_caller(accounts().bob);
+            assert_eq!(
+                contract.accept_swap(id, 50, 100),
+                Err(Error::TransferFailed)
+            );
+
+            assert_eq!(mock::balance(token_b(), accounts().bob), 1_000);
+            assert_eq!(mock::balance(token_b(), accounts().alice), 1_000);
+        }
+
+        #[ink::test]
+        fn my_open_fills_lists_live_swaps_the_caller_partly_filled() {
+            let mut contract = setup();
+            let bob = accounts().bob;
+            let ids: Vec<u64> = (0..4).map(|_| create(&mut contract, 100, 200)).collect();
+
+            set_caller(bob);
+            assert_eq!(contract.accept_swap(ids[0], 10, 20), Ok(()));
+            assert_eq!(contract.accept_swap(ids[2], 10, 20), Ok(()));
+            assert_eq!(contract.accept_swap(ids[3], 100, 200), Ok(()));
+            set_caller(accounts().charlie);
+            assert_eq!(contract.accept_swap(ids[1], 10, 20), Ok(()));
+
+            assert_eq!(contract.my_open_fills(bob), [ids[0], ids[2]]);
+            assert_eq!(contract.my_open_fills(accounts().charlie), [ids[1]]);
+        }
+
+        #[ink::test]
+        fn config_reflects_the_current_settings() {
+            let mut contract = setup();
+            let oracle = AccountId::from([0x0E; 32]);
+            assert_eq!(contract.set_allow_self_accept(true), Ok(()));
+            assert_eq!(contract.set_oracle(Some(oracle)), Ok(()));
+            assert_eq!(contract.set_creation_fee(5), Ok(()));
+            assert_eq!(contract.set_max_duration(50), Ok(()));
+
+            let config = contract.config();
+            assert_eq!(config.owner, accounts().alice);
+            assert!(config.allow_self_accept);
+            assert_eq!(config.oracle, Some(oracle));
+            assert_eq!(config.creation_fee, 5);
+            assert_eq!(config.max_duration, 50);
+            assert_eq!(config.delegated_contract, None);
+            assert!(!config.paused);
+        }
+
+        #[ink::test]
+        fn average_price_sums_uneven_fills() {
+            let mut contract = setup();
+            let id = create(&mut contract, 100, 200);
+            set_caller(accounts().bob);
+            assert_eq!(contract.accept_swap(id, 10, 20), Ok(()));
+            // Paying above the listed price is allowed and shows in the realized price.
+            assert_eq!(contract.accept_swap(id, 30, 70), Ok(()));
+            assert_eq!(contract.average_price(id), Ok((40, 90)));
+            assert_eq!(contract.average_price(id + 1), Err(Error::SwapNotFound));
+        }
+
+        #[ink::test]
+        fn get_swaps_returns_none_for_absent_ids() {
+            let mut contract = setup();
+            let first = create(&mut contract, 100, 200);
+            let second = create(&mut contract, 10, 20);
+            assert_eq!(contract.cancel_and_refund(first), Ok(()));
+
+            let swaps = contract.get_swaps(vec![first, second, 99]);
+            assert_eq!(swaps.len(), 3);
+            assert!(swaps[0].is_none());
+            assert_eq!(swaps[1].as_ref().map(|swap| swap.required_a), Some(10));
+            assert!(swaps[2].is_none());
+
+            let many = contract.get_swaps(vec![second; MAX_SWAPS_PER_QUERY + 5]);
+            assert_eq!(many.len(), MAX_SWAPS_PER_QUERY);
+        }
+
+        #[ink::test]
+        fn target_ratio_is_unchanged_by_fills() {
+            let mut contract = setup();
+            let id = create(&mut contract, 100, 200);
+            set_caller(accounts().bob);
+            assert_eq!(contract.accept_swap(id, 25, 60), Ok(()));
+            assert_eq!(contract.target_ratio(id), Ok((100, 200)));
+            assert_eq!(contract.target_ratio(id + 1), Err(Error::SwapNotFound));
+        }
+
+        #[ink::test]
+        fn delete_swap_to_refunds_the_given_address() {
+            let mut contract = setup();
+            let charlie = accounts().charlie;
+            let id = create(&mut contract, 100, 200);
+            assert_eq!(
+                contract.delete_swap_to(id, AccountId::from([0u8; 32])),
+                Err(Error::ZeroAddress)
+            );
+
+            assert_eq!(contract.delete_swap_to(id, charlie), Ok(()));
+            assert_eq!(mock::balance(token_a(), charlie), 1_100);
+            assert_eq!(mock::balance(token_a(), accounts().alice), 900);
+        }
+
+        #[ink::test]
+        fn creator_cannot_self_accept_unless_allowed() {
+            let mut contract = setup();
+            let id = create(&mut contract, 100, 200);
+            assert_eq!(contract.accept_swap(id, 10, 20), Err(Error::SelfAccept));
+
+            assert_eq!(contract.set_allow_self_accept(true), Ok(()));
+            assert_eq!(contract.accept_swap(id, 10, 20), Ok(()));
+        }
+
+        #[ink::test]
+        fn proceeds_report_token_b_collected_so_far() {
+            let mut contract = setup();
+            let id = create(&mut contract, 100, 200);
+            assert_eq!(contract.proceeds(id), Ok(0));
+            set_caller(accounts().bob);
+            assert_eq!(contract.accept_swap(id, 30, 60), Ok(()));
+            assert_eq!(contract.proceeds(id), Ok(60));
+            assert_eq!(mock::balance(token_b(), accounts().alice), 1_060);
+        }
+
+        #[ink::test]
+        fn supported_selectors_lists_the_token_selectors() {
+            let contract = setup();
+            assert_eq!(
+                contract.supported_selectors(),
+                [
+                    BALANCE_OF_SELECTOR,
+                    TRANSFER_SELECTOR,
+                    TOTAL_SUPPLY_SELECTOR,
+                    TOKEN_SYMBOL_SELECTOR
+                ]
+            );
+        }
+
+        #[ink::test]
+        fn accept_by_b_derives_amount_a_from_the_listed_ratio() {
+            let mut contract = setup();
+            let id = create(&mut contract, 100, 300);
+            set_caller(accounts().bob);
+            // 50 * 100 / 300 rounds down to 16.
+            assert_eq!(contract.accept_swap_by_b(id, 50), Ok(16));
+            assert_eq!(contract.remaining_amounts(id), Ok((84, 250)));
+            assert_eq!(mock::balance(token_a(), accounts().bob), 1_016);
+        }
+
+        #[ink::test]
+        fn accept_by_a_rounds_the_payment_up() {
+            let mut contract = setup();
+            let bob = accounts().bob;
+            let id = create(&mut contract, 3, 10);
+            set_caller(bob);
+            // 1 * 10 / 3 rounds up to 4.
+            assert_eq!(contract.accept_swap_by_a(id, 1), Ok(4));
+            assert_eq!(mock::balance(token_a(), bob), 1_001);
+            assert_eq!(mock::balance(token_b(), bob), 996);
+            assert_eq!(
+                contract.accept_swap_by_a(id, 3),
+                Err(Error::InsufficientBalance)
+            );
+        }
+
+        #[ink::test]
+        fn reserved_for_me_counts_live_swaps_restricted_to_the_account() {
+            let mut contract = setup();
+            let (bob, charlie) = (accounts().bob, accounts().charlie);
+            for acceptor in [Some(bob), Some(bob), Some(charlie), None, Some(bob)] {
+                contract
+                    .create_swap(token_a(), token_b(), 10, 20, 100, acceptor, false)
+                    .unwrap();
+            }
+            assert_eq!(contract.cancel_and_refund(4), Ok(()));
+
+            assert_eq!(contract.reserved_for_me_count(bob), 2);
+            assert_eq!(contract.reserved_for_me_count(charlie), 1);
+            assert_eq!(contract.reserved_for_me_count(accounts().django), 0);
+        }
+
+        #[ink::test]
+        fn create_rejects_amounts_above_the_total_supply_when_enabled() {
+            let mut contract = setup();
+            mock::with(|chain| {
+                chain.supplies.insert(token_a(), 500);
+                chain.supplies.insert(token_b(), 5_000);
+            });
+            let create_with = |contract: &mut TokenSwap, amount_a| {
+                contract.create_swap(token_a(), token_b(), amount_a, 20, 100, None, false)
+            };
+            assert!(create_with(&mut contract, 600).is_ok());
+
+            assert_eq!(contract.set_check_total_supply(true), Ok(()));
+            assert_eq!(
+                create_with(&mut contract, 501),
+                Err(Error::AmountExceedsSupply)
+            );
+            assert!(create_with(&mut contract, 300).is_ok());
+        }
+
+        #[ink::test]
+        fn scan_cost_hint_counts_every_issued_id() {
+            let mut contract = setup();
+            assert_eq!(contract.scan_cost_hint(), 0);
+            let id = create(&mut contract, 10, 20);
+            create(&mut contract, 10, 20);
+            assert_eq!(contract.cancel_and_refund(id), Ok(()));
+            assert_eq!(contract.scan_cost_hint(), 2);
+        }
+
+        #[ink::test]
+        fn escrowed_token_count_counts_distinct_offered_tokens() {
+            let mut contract = setup();
+            assert_eq!(contract.escrowed_token_count(), 0);
+            create(&mut contract, 10, 20);
+            create(&mut contract, 30, 40);
+            contract
+                .create_swap(token_b(), token_a(), 10, 20, 100, None, false)
+                .unwrap();
+            assert_eq!(contract.escrowed_token_count(), 2);
+        }
+
+        #[ink::test]
+        fn notes_are_readable_by_creator_and_allowed_acceptor_only() {
+            let mut contract = setup();
+            let bob = accounts().bob;
+            let id = contract
+                .create_swap(token_a(), token_b(), 10, 20, 100, Some(bob), false)
+                .unwrap();
+            assert_eq!(contract.set_note(id, Some(b"ciphertext".to_vec())), Ok(()));
+            assert_eq!(
+                contract.set_note(id, Some(vec![0; MAX_NOTE_LEN + 1])),
+                Err(Error::NoteTooLong)
+            );
+            assert_eq!(contract.get_note(id), Ok(Some(b"ciphertext".to_vec())));
+
+            set_caller(bob);
+            assert_eq!(contract.get_note(id), Ok(Some(b"ciphertext".to_vec())));
+            assert_eq!(contract.set_note(id, None), Err(Error::Unauthorized));
+
+            set_caller(accounts().charlie);
+            assert_eq!(contract.get_note(id), Err(Error::Unauthorized));
+        }
+
+        #[ink::test]
+        fn accept_rejects_swaps_with_a_zero_required_amount() {
+            let mut contract = setup();
+            let id = create(&mut contract, 100, 200);
+            let mut swap = contract.get_swap(id).unwrap();
+            swap.required_b = 0;
+            contract.swaps.insert(id, &swap);
+
+            set_caller(accounts().bob);
+            assert_eq!(contract.accept_swap(id, 10, 0), Err(Error::InvalidAmount));
+            assert_eq!(contract.accept_swap_by_b(id, 0), Err(Error::InvalidAmount));
+        }
+
+        #[ink::test]
+        fn completed_count_counts_fully_filled_swaps_per_creator() {
+            let mut contract = setup();
+            let ids: Vec<u64> = (0..3).map(|_| create(&mut contract, 10, 20)).collect();
+            set_caller(accounts().bob);
+            assert_eq!(contract.accept_swap(ids[0], 10, 20), Ok(()));
+            assert_eq!(contract.accept_swap(ids[1], 5, 10), Ok(()));
+            assert_eq!(contract.accept_swap(ids[1], 5, 10), Ok(()));
+            assert_eq!(contract.accept_swap(ids[2], 5, 10), Ok(()));
+
+            assert_eq!(contract.completed_count(accounts().alice), 2);
+            assert_eq!(contract.completed_count(accounts().bob), 0);
+        }
+
+        #[ink::test]
+        fn next_swap_id_is_the_id_of_the_next_create() {
+            let mut contract = setup();
+            assert_eq!(contract.next_swap_id(), 0);
+            for _ in 0..3 {
+                let expected = contract.next_swap_id();
+                assert_eq!(create(&mut contract, 10, 20), expected);
+            }
+            assert_eq!(contract.cancel_and_refund(2), Ok(()));
+            assert_eq!(contract.next_swap_id(), 3);
+        }
+
+        // Runs from inside the token A payout; a new instance shares the Mapping cells, so it
+        // sees what the outer accept has already written.
+        fn assert_fill_recorded() {
+            let contract = TokenSwap::new();
+            assert_eq!(contract.get_swap(0).unwrap().accepted_a, 40);
+            assert_eq!(contract.fills.get((accounts().bob, 0)), Some((40, 80)));
+        }
+
+        #[ink::test]
+        fn fill_is_recorded_before_any_token_moves() {
+            let mut contract = setup();
+            let id = create(&mut contract, 100, 200);
+            mock::with(|chain| chain.on_transfer = Some(assert_fill_recorded));
+
+            set_caller(accounts().bob);
+            assert_eq!(contract.accept_swap(id, 40, 80), Ok(()));
+            assert!(mock::with(|chain| chain.on_transfer.is_none()));
+        }
+
+        #[ink::test]
+        fn expiry_histogram_buckets_live_swaps_by_blocks_left() {
+            let mut contract = setup();
+            for duration in [5, 9, 10, 25, 100] {
+                contract
+                    .create_swap(token_a(), token_b(), 10, 20, duration, None, false)
+                    .unwrap();
+            }
+            assert_eq!(contract.expiry_histogram(10, 3), [2, 1, 1]);
+            assert_eq!(contract.expiry_histogram(0, 3), Vec::<u32>::new());
+
+            for _ in 0..6 {
+                test::advance_block::<DefaultEnvironment>();
+            }
+            assert_eq!(contract.expiry_histogram(10, 3), [2, 1, 0]);
+        }
+
+        #[ink::test]
+        fn create_rejects_prices_outside_the_oracle_band() {
+            let mut contract = setup();
+            assert_eq!(
+                contract.set_oracle(Some(AccountId::from([0x0E; 32]))),
+                Ok(())
+            );
+            assert_eq!(contract.set_max_deviation_bps(500), Ok(()));
+            mock::with(|chain| chain.price = Some(2 * PRICE_SCALE));
+
+            let create_at = |contract: &mut TokenSwap, amount_b, force| {
+                contract.create_swap(token_a(), token_b(), 100, amount_b, 100, None, force)
+            };
+            assert!(create_at(&mut contract, 204, false).is_ok());
+            assert_eq!(
+                create_at(&mut contract, 250, false),
+                Err(Error::PriceOutOfBand)
+            );
+            assert!(create_at(&mut contract, 250, true).is_ok());
+
+            mock::with(|chain| chain.price = None);
+            assert_eq!(create_at(&mut contract, 200, false), Err(Error::CallFailed));
+        }
+
+        #[ink::test]
+        fn native_accept_wraps_the_payment_for_the_creator() {
+            let mut contract = setup();
+            let id = create(&mut contract, 100, 200);
+            let bob = accounts().bob;
+            set_caller(bob);
+            assert_eq!(
+                contract.accept_swap_with_native(id, 10, 20),
+                Err(Error::NoNativeWrapper)
+            );
+
+            set_caller(accounts().alice);
+            assert_eq!(contract.set_native_wrapper(Some(token_b())), Ok(()));
+            set_caller(bob);
+            test::set_value_transferred::<DefaultEnvironment>(19);
+            assert_eq!(
+                contract.accept_swap_with_native(id, 10, 20),
+                Err(Error::InvalidAmount)
+            );
+
+            test::set_value_transferred::<DefaultEnvironment>(20);
+            assert_eq!(contract.accept_swap_with_native(id, 10, 20), Ok(()));
+            assert_eq!(mock::balance(token_a(), bob), 1_010);
+            assert_eq!(mock::balance(token_b(), bob), 1_000);
+            assert_eq!(mock::balance(token_b(), accounts().alice), 1_020);
+        }
+
+        #[ink::test]
+        fn open_counts_reports_each_creators_open_swaps() {
+            let mut contract = setup();
+            let (alice, bob, charlie) = (accounts().alice, accounts().bob, accounts().charlie);
+            for creator in [alice, alice, bob, alice] {
+                set_caller(creator);
+                contract
+                    .create_swap(token_a(), token_b(), 10, 20, 100, None, false)
+                    .unwrap();
+            }
+            set_caller(alice);
+            assert_eq!(contract.cancel_and_refund(0), Ok(()));
+
+            assert_eq!(contract.open_counts(vec![alice, bob, charlie]), [2, 1, 0]);
+            let many = contract.open_counts(vec![alice; MAX_ACCOUNTS_PER_QUERY + 1]);
+            assert_eq!(many.len(), MAX_ACCOUNTS_PER_QUERY);
+        }
+
+        #[ink::test]
+        fn per_token_cap_is_freed_by_a_delete() {
+            let mut contract = setup();
+            assert_eq!(contract.set_max_open_per_token(2), Ok(()));
+            let first = create(&mut contract, 10, 20);
+            create(&mut contract, 10, 20);
+            assert_eq!(
+                contract.create_swap(token_a(), token_b(), 10, 20, 100, None, false),
+                Err(Error::TokenSwapLimitReached)
+            );
+            // The cap is per offered token.
+            assert!(contract
+                .create_swap(token_b(), token_a(), 10, 20, 100, None, false)
+                .is_ok());
+
+            assert_eq!(contract.delete_swap(first), Ok(()));
+            create(&mut contract, 10, 20);
+        }
+
+        #[ink::test]
+        fn accept_event_carries_the_stored_remaining_amounts() {
+            let mut contract = setup();
+            let id = create(&mut contract, 100, 200);
+            set_caller(accounts().bob);
+            assert_eq!(contract.accept_swap(id, 30, 60), Ok(()));
+
+            let Some(Event::SwapAccepted(accepted)) = events().pop() else {
+                panic!("no SwapAccepted event");
+            };
+            assert_eq!(
+                (accepted.remaining_a, accepted.remaining_b),
+                contract.remaining_amounts(id).unwrap()
+            );
+            assert_eq!((accepted.remaining_a, accepted.remaining_b), (70, 140));
+        }
+
+        #[ink::test]
+        fn active_count_excludes_deleted_and_filled_swaps() {
+            let mut contract = setup();
+            let ids: Vec<u64> = (0..3).map(|_| create(&mut contract, 10, 20)).collect();
+            assert_eq!(contract.cancel_and_refund(ids[0]), Ok(()));
+            set_caller(accounts().bob);
+            assert_eq!(contract.accept_swap(ids[1], 10, 20), Ok(()));
+
+            assert_eq!(contract.active_swap_count(), 1);
+            assert_eq!(contract.next_swap_id(), 3);
+        }
+
+        #[ink::test]
+        fn pair_symbols_reads_and_caches_token_symbols() {
+            let mut contract = setup();
+            let id = create(&mut contract, 10, 20);
+            mock::with(|chain| chain.symbols.insert(token_a(), b"AAA".to_vec()));
+            assert_eq!(contract.pair_symbols(id), Ok((b"AAA".to_vec(), Vec::new())));
+
+            // Cached after the first read, so a later change is not picked up.
+            mock::with(|chain| chain.symbols.insert(token_a(), b"NEW".to_vec()));
+            assert_eq!(contract.pair_symbols(id).unwrap().0, b"AAA".to_vec());
+            assert_eq!(contract.pair_symbols(id + 1), Err(Error::SwapNotFound));
+        }
+
+        #[ink::test]
+        fn list_swaps_skips_deleted_ids() {
+            let mut contract = setup();
+            let ids: Vec<u64> = (0..5).map(|_| create(&mut contract, 10, 20)).collect();
+            assert_eq!(contract.cancel_and_refund(ids[1]), Ok(()));
+            assert_eq!(contract.cancel_and_refund(ids[2]), Ok(()));
+
+            let page = |contract: &TokenSwap, start, limit| {
+                contract
+                    .list_swaps(start, limit)
+                    .into_iter()
+                    .map(|(id, _)| id)
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(page(&contract, 0, 3), [ids[0]]);
+            assert_eq!(page(&contract, 3, 3), [ids[3], ids[4]]);
+            assert_eq!(page(&contract, 5, 3), Vec::<u64>::new());
+        }
+
+        #[ink::test]
+        fn create_rejects_identical_tokens_and_zero_amounts() {
+            let mut contract = setup();
+            let mut create_with = |token_b, amount_a, amount_b| {
+                contract.create_swap(token_a(), token_b, amount_a, amount_b, 100, None, false)
+            };
+            assert_eq!(create_with(token_a(), 10, 20), Err(Error::IdenticalTokens));
+            assert_eq!(create_with(token_b(), 0, 20), Err(Error::ZeroAmount));
+            assert_eq!(create_with(token_b(), 10, 0), Err(Error::ZeroAmount));
+            assert_eq!(create_with(token_b(), 0, 0), Err(Error::ZeroAmount));
+            assert!(create_with(token_b(), 10, 20).is_ok());
+        }
+
+        #[ink::test]
+        fn anyone_reaps_an_expired_swap_back_to_its_creator() {
+            let mut contract = setup();
+            let id = create(&mut contract, 100, 200);
+            set_caller(accounts().bob);
+            assert_eq!(contract.accept_swap(id, 40, 80), Ok(()));
+            assert_eq!(contract.reap_expired(id), Err(Error::SwapNotExpired));
+
+            for _ in 0..101 {
+                test::advance_block::<DefaultEnvironment>();
+            }
+            set_caller(accounts().charlie);
+            assert_eq!(contract.reap_expired(id), Ok(()));
+            assert_eq!(mock::balance(token_a(), accounts().alice), 960);
+            assert!(matches!(
+                events().last(),
+                Some(Event::SwapExpiredReaped(reaped))
+                    if reaped.id == id && reaped.reaper == accounts().charlie
+            ));
+            assert_eq!(contract.reap_expired(id), Err(Error::SwapNotFound));
+        }
+
+        #[ink::test]
+        fn acceptor_short_on_token_b_receives_nothing() {
+            let mut contract = setup();
+            let id = create(&mut contract, 100, 200);
+            let bob = accounts().bob;
+            mock::with(|chain| chain.balances.insert((token_b(), bob), 150));
+            set_caller(bob);
+            assert_eq!(
+                contract.accept_swap(id, 100, 200),
+                Err(Error::InsufficientBalance)
+            );
+            assert_eq!(mock::balance(token_a(), bob), 1_000);
+            assert_eq!(mock::balance(token_a(), contract_id()), 100);
+        }
+
+        #[ink::test]
+        fn swap_exists_tracks_stored_swaps() {
+            let mut contract = setup();
+            let id = create(&mut contract, 10, 20);
+            assert!(contract.swap_exists(id));
+            assert!(!contract.swap_exists(id + 1));
+        }
+
+        #[ink::test]
+        fn total_fills_counts_successful_accepts_across_swaps() {
+            let mut contract = setup();
+            let first = create(&mut contract, 100, 200);
+            let second = create(&mut contract, 10, 20);
+            set_caller(accounts().bob);
+            assert_eq!(contract.accept_swap(first, 10, 20), Ok(()));
+            assert_eq!(contract.accept_swap(second, 5, 10), Ok(()));
+            assert_eq!(contract.accept_swap(first, 10, 20), Ok(()));
+            assert_eq!(contract.accept_swap(first, 10, 0), Err(Error::UnderPriced));
+            assert_eq!(contract.total_fills(), 3);
+        }
+
+        #[ink::test]
+        fn accept_enforces_the_updated_allowed_acceptor() {
+            let mut contract = setup();
+            let (bob, charlie) = (accounts().bob, accounts().charlie);
+            let id = create(&mut contract, 100, 200);
+
+            assert_eq!(contract.set_allowed_acceptor(id, Some(bob)), Ok(()));
+            set_caller(charlie);
+            assert_eq!(contract.accept_swap(id, 10, 20), Err(Error::Unauthorized));
+            assert_eq!(
+                contract.set_allowed_acceptor(id, None),
+                Err(Error::Unauthorized)
+            );
+            set_caller(bob);
+            assert_eq!(contract.accept_swap(id, 10, 20), Ok(()));
+
+            set_caller(accounts().alice);
+            assert_eq!(contract.set_allowed_acceptor(id, None), Ok(()));
+            set_caller(charlie);
+            assert_eq!(contract.accept_swap(id, 10, 20), Ok(()));
+        }
+
+        #[ink::test]
+        fn accept_event_matches_the_call_arguments() {
+            let mut contract = setup();
+            let id = create(&mut contract, 100, 200);
+            set_caller(accounts().bob);
+            assert_eq!(contract.accept_swap(id, 10, 20), Ok(()));
+            assert_eq!(contract.accept_swap(id, 15, 30), Ok(()));
+
+            let Some(Event::SwapAccepted(accepted)) = events().pop() else {
+                panic!("no SwapAccepted event");
+            };
+            assert_eq!(accepted.id, id);
+            assert_eq!(accepted.acceptor, accounts().bob);
+            assert_eq!((accepted.amount_a, accepted.amount_b), (15, 30));
+            assert_eq!((accepted.accepted_a, accepted.accepted_b), (25, 50));
+            assert!(!accepted.completed);
+        }
+
+        #[ink::test]
+        fn open_liquidity_sums_unfilled_token_a() {
+            let mut contract = setup();
+            let first = create(&mut contract, 100, 200);
+            create(&mut contract, 50, 100);
+            contract
+                .create_swap(token_b(), token_a(), 70, 10, 100, None, false)
+                .unwrap();
+            set_caller(accounts().bob);
+            assert_eq!(contract.accept_swap(first, 30, 60), Ok(()));
+
+            assert_eq!(contract.open_liquidity(token_a()), 120);
+            assert_eq!(contract.open_liquidity(token_b()), 70);
+        }
+
+        #[ink::test]
+        fn delegated_create_forwards_the_allowed_acceptor() {
+            let mut contract = setup();
+            let (alice, bob) = (accounts().alice, accounts().bob);
+            assert_eq!(
+                contract.set_delegated_contract(AccountId::from([0xDE; 32])),
+                Ok(())
+            );
+            contract
+                .create_swap(token_a(), token_b(), 10, 20, 100, Some(bob), false)
+                .unwrap();
+            let charlie = accounts().charlie;
+            set_caller(charlie);
+            contract
+                .create_swap(token_a(), token_b(), 10, 20, 100, None, false)
+                .unwrap();
+
+            assert_eq!(
+                mock::with(|chain| chain.delegated.clone()),
+                [(alice, Some(bob)), (charlie, None)]
+            );
+            // Nothing is escrowed locally.
+            assert_eq!(mock::balance(token_a(), contract_id()), 0);
+            assert_eq!(contract.active_swap_count(), 0);
+        }
+
+        #[ink::test]
+        fn delegated_create_returns_the_delegate_assigned_id() {
+            let mut contract = setup();
+            assert_eq!(
+                contract.set_delegated_contract(AccountId::from([0xDE; 32])),
+                Ok(())
+            );
+            mock::with(|chain| chain.delegate_id = 41);
+            assert_eq!(
+                contract.create_swap(token_a(), token_b(), 10, 20, 100, None, false),
+                Ok(41)
+            );
+            assert_eq!(contract.next_swap_id(), 0);
+        }
+
+        #[ink::test]
+        fn remaining_amounts_after_a_partial_fill() {
+            let mut contract = setup();
+            let id = create(&mut contract, 100, 200);
+            assert_eq!(contract.remaining_amounts(id), Ok((100, 200)));
+            set_caller(accounts().bob);
+            assert_eq!(contract.accept_swap(id, 35, 70), Ok(()));
+            assert_eq!(contract.remaining_amounts(id), Ok((65, 130)));
+            assert_eq!(contract.remaining_amounts(id + 1), Err(Error::SwapNotFound));
+        }
+
+        #[ink::test]
+        fn freeze_blocks_every_swap_mutation() {
+            let mut contract = setup();
+            let id = create(&mut contract, 100, 200);
+            assert_eq!(contract.freeze_all(), Ok(()));
+
+            assert_eq!(
+                contract.create_swap(token_a(), token_b(), 10, 20, 100, None, false),
+                Err(Error::Frozen)
+            );
+            assert_eq!(contract.delete_swap(id), Err(Error::Frozen));
+            assert_eq!(
+                contract.delete_swap_to(id, accounts().bob),
+                Err(Error::Frozen)
+            );
+            assert_eq!(contract.set_allowed_acceptor(id, None), Err(Error::Frozen));
+            assert_eq!(contract.set_note(id, None), Err(Error::Frozen));
+            assert_eq!(contract.set_acceptor_callback(id, true), Err(Error::Frozen));
+            assert_eq!(contract.set_min_fill(id, 1, 1), Err(Error::Frozen));
+            assert_eq!(contract.set_oracle_settlement(id, None), Err(Error::Frozen));
+            for _ in 0..101 {
+                test::advance_block::<DefaultEnvironment>();
+            }
+            assert_eq!(contract.reap_expired(id), Err(Error::Frozen));
+
+            set_caller(accounts().bob);
+            assert_eq!(contract.accept_swap(id, 10, 20), Err(Error::Frozen));
+            assert_eq!(
+                contract.accept_swaps(vec![(id, 10, 20)]),
+                Err(Error::Frozen)
+            );
+
+            set_caller(accounts().alice);
+            assert_eq!(contract.unfreeze(), Ok(()));
+            assert_eq!(contract.delete_swap(id), Ok(()));
+        }
+
+        #[ink::test]
+        fn fills_may_not_leave_a_dust_remainder() {
+            let mut contract = setup();
+            assert_eq!(contract.set_complete_if_below(10), Ok(()));
+            let id = create(&mut contract, 100, 100);
+            set_caller(accounts().bob);
+            assert_eq!(
+                contract.accept_swap(id, 91, 91),
+                Err(Error::MustFillEntirely)
+            );
+            assert_eq!(contract.accept_swap(id, 90, 90), Ok(()));
+            assert_eq!(contract.accept_swap(id, 10, 10), Ok(()));
+            assert!(!contract.swap_exists(id));
+        }
+
+        #[ink::test]
+        fn create_caps_the_duration() {
+            let mut contract = setup();
+            assert_eq!(contract.set_max_duration(50), Ok(()));
+            let mut create_for = |duration| {
+                contract.create_swap(token_a(), token_b(), 10, 20, duration, None, false)
+            };
+            assert!(create_for(49).is_ok());
+            assert!(create_for(50).is_ok());
+            assert_eq!(create_for(51), Err(Error::DurationTooLong));
+        }
+
+        #[ink::test]
+        fn fills_at_18_decimal_sizes_do_not_overflow() {
+            const UNIT: Balance = 1_000_000_000_000_000_000;
+            let mut contract = setup();
+            let (alice, bob) = (accounts().alice, accounts().bob);
+            mock::mint(token_a(), alice, 1_000 * UNIT);
+            mock::mint(token_b(), alice, 1_000 * UNIT);
+            mock::mint(token_b(), bob, 1_000 * UNIT);
+            let id = create(&mut contract, 20 * UNIT, 40 * UNIT);
+
+            assert_eq!(
+                contract.accept_precheck(id, bob, 10 * UNIT, 20 * UNIT),
+                Ok(())
+            );
+            assert_eq!(
+                contract.accept_precheck(id, bob, 10 * UNIT, 20 * UNIT - 1),
+                Err(Error::UnderPriced)
+            );
+            set_caller(bob);
+            assert_eq!(contract.accept_swap(id, 10 * UNIT, 20 * UNIT), Ok(()));
+            assert_eq!(contract.accept_swap(id, 10 * UNIT, 20 * UNIT), Ok(()));
+            assert!(!contract.swap_exists(id));
+        }
+
+        #[ink::test]
+        fn closing_fill_pays_exactly_the_remainder_after_rounded_fills() {
+            let mut contract = setup();
+            let id = create(&mut contract, 3, 10);
+            set_caller(accounts().bob);
+            assert_eq!(contract.accept_swap_by_a(id, 1), Ok(4));
+            assert_eq!(contract.accept_swap_by_a(id, 1), Ok(4));
+            assert_eq!(contract.remaining_amounts(id), Ok((1, 2)));
+
+            assert_eq!(contract.accept_swap(id, 1, 1), Err(Error::UnderPriced));
+            assert_eq!(contract.accept_swap(id, 1, 2), Ok(()));
+            assert_eq!(contract.proceeds(id), Ok(10));
+        }
+
+        #[ink::test]
+        fn accept_by_b_rejects_a_payment_too_small_for_any_token_a() {
+            let mut contract = setup();
+            let id = create(&mut contract, 3, 10);
+            let bob = accounts().bob;
+            set_caller(bob);
+            assert_eq!(contract.accept_swap_by_b(id, 2), Err(Error::ZeroAmount));
+            assert_eq!(contract.accept_swap(id, 0, 10), Err(Error::ZeroAmount));
+            assert_eq!(mock::balance(token_b(), bob), 1_000);
+            assert_eq!(contract.accept_swap_by_b(id, 10), Ok(3));
+        }
+
+        #[ink::test]
+        fn accept_by_b_handles_18_decimal_sizes() {
+            const UNIT: Balance = 1_000_000_000_000_000_000;
+            let mut contract = setup();
+            let (alice, bob) = (accounts().alice, accounts().bob);
+            mock::mint(token_a(), alice, 1_000 * UNIT);
+            mock::mint(token_b(), alice, 1_000 * UNIT);
+            mock::mint(token_b(), bob, 1_000 * UNIT);
+            let id = create(&mut contract, 30 * UNIT, 90 * UNIT);
+            set_caller(bob);
+            assert_eq!(contract.accept_swap_by_b(id, 45 * UNIT), Ok(15 * UNIT));
+        }
+
+        #[ink::test]
+        fn short_escrowed_swap_completes_when_its_escrow_is_filled() {
+            let mut contract = setup();
+            mock::with(|chain| chain.transfer_fees.insert(token_a(), 5));
+            let id = create(&mut contract, 100, 200);
+            assert_eq!(contract.remaining_amounts(id), Ok((95, 200)));
+            assert_eq!(contract.open_liquidity(token_a()), 95);
+
+            set_caller(accounts().bob);
+            assert_eq!(contract.accept_swap(id, 95, 190), Ok(()));
+            assert_eq!(contract.get_swap(id), Err(Error::SwapNotFound));
+            assert_eq!(contract.remaining_amounts(id), Err(Error::SwapNotFound));
+            assert_eq!(contract.completed_count(accounts().alice), 1);
+            assert!(!contract.has_escrow());
+        }
+
+        #[ink::test]
+        fn cross_contract_calls_forward_at_most_the_gas_cap() {
+            let mut contract = setup();
+            let forwarded = || {
+                mock::with(|chain| {
+                    chain
+                        .calls
+                        .drain(..)
+                        .map(|(_, gas)| gas)
+                        .collect::<Vec<_>>()
+                })
+            };
+
+            assert_eq!(contract.set_call_gas_limit(7_000), Ok(()));
+            assert_eq!(contract.set_max_call_gas_limit(5_000), Ok(()));
+            assert_eq!(contract.effective_xcall_gas(), 5_000);
+            forwarded();
+            create(&mut contract, 100, 200);
+            let gas = forwarded();
+            assert!(!gas.is_empty());
+            assert!(gas.iter().all(|&g| g == 5_000));
+
+            // A limit of 0 would forward all remaining gas, so it is clamped as well.
+            assert_eq!(contract.set_call_gas_limit(0), Ok(()));
+            assert_eq!(contract.effective_xcall_gas(), 5_000);
+            assert_eq!(contract.set_call_gas_limit(3_000), Ok(()));
+            assert_eq!(contract.effective_xcall_gas(), 3_000);
+
+            assert_eq!(
+                contract.set_config(ConfigUpdate {
+                    max_call_gas_limit: Some(0),
+                    ..Default::default()
+                }),
+                Ok(())
+            );
+            assert_eq!(contract.set_call_gas_limit(0), Ok(()));
+            assert_eq!(contract.effective_xcall_gas(), 0);
+
+            set_caller(accounts().bob);
+            assert_eq!(contract.set_max_call_gas_limit(1), Err(Error::Unauthorized));
+        }
+
+        #[ink::test]
+        fn set_config_applies_several_settings_at_once() {
+            let mut contract = setup();
+            let update = ConfigUpdate {
+                creation_fee: Some(5),
+                call_gas_limit: Some(7_000),
+                ..Default::default()
+            };
+
+            set_caller(accounts().bob);
+            assert_eq!(
+                contract.set_config(update.clone()),
+                Err(Error::Unauthorized)
+            );
+            assert_eq!(contract.config().creation_fee, 0);
+
+            set_caller(accounts().alice);
+            assert_eq!(contract.set_config(update), Ok(()));
+            let config = contract.config();
+            assert_eq!((config.creation_fee, config.call_gas_limit), (5, 7_000));
+            assert_eq!(contract.effective_xcall_gas(), 7_000);
+        }
+
+        #[ink::test]
+        fn min_fill_is_fixed_after_the_first_fill() {
+            let mut contract = setup();
+            let id = create(&mut contract, 100, 200);
+            assert_eq!(contract.set_min_fill(id, 10, 20), Ok(()));
+
+            set_caller(accounts().bob);
+            assert_eq!(contract.accept_swap(id, 10, 20), Ok(()));
+
+            set_caller(accounts().alice);
+            assert_eq!(
+                contract.set_min_fill(id, 50, 100),
+                Err(Error::SwapAlreadyFilled)
+            );
+            assert_eq!(
+                contract.set_min_fill(id, 0, 0),
+                Err(Error::SwapAlreadyFilled)
+            );
+            set_caller(accounts().bob);
+            assert_eq!(contract.accept_swap(id, 5, 10), Err(Error::FillTooSmall));
+            assert_eq!(contract.accept_swap(id, 20, 40), Ok(()));
+        }
+    }
+
+    // Needs a running substrate-contracts-node; run with `cargo test --features e2e-tests`.
+    #[cfg(all(test, feature = "e2e-tests"))]
+    mod e2e_tests {
+        use super::*;
+        use ink_e2e::build_message;
+
+        type E2EResult<T> = std::result::Result<T, Box<dyn std::error::Error>>;
+
+        // own_code_hash is not implemented by the off-chain engine, so code_hash is only
+        // checked against a node: instances of the same code report the same hash.
+        #[ink_e2e::test]
+        async fn code_hash_is_shared_by_instances_of_the_same_code(
+            mut client: ink_e2e::Client<C, E>,
+        ) -> E2EResult<()> {
+            let mut hashes = Vec::new();
+            // Each instantiate picks a fresh salt, so these are two distinct contracts.
+            for _ in 0..2 {
+                let account_id = client
+                    .instantiate(
+                        "contractotc",
+                        &ink_e2e::alice(),
+                        TokenSwapRef::new(),
+                        0,
+                        None,
+                    )
+                    .await
+                    .expect("instantiate failed")
+                    .account_id;
+                let code_hash = build_message::<TokenSwapRef>(account_id.clone())
+                    .call(|contract| contract.code_hash());
+                let result = client
+                    .call_dry_run(&ink_e2e::alice(), &code_hash, 0, None)
+                    .await;
+                hashes.push(result.return_value().expect("code_hash failed"));
+            }
+
+            assert_eq!(hashes[0], hashes[1]);
+            assert_ne!(hashes[0], Hash::from([0; 32]));
+            Ok(())
+        }
     }
 }